@@ -0,0 +1,58 @@
+//! testdata のチャンクを 1 件ずつ保存する場合とまとめて保存する場合のスループットの比較
+//!
+//! 接続先は設定ファイルや環境変数に従う ( e.g. `LVL_STORE_BACKEND=local LVL_EMBEDDING_BACKEND=hash cargo bench` でオフライン )
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use local_vectored_llm::chroma::document::Document;
+use local_vectored_llm::config::{Config, ConfigArgs};
+use local_vectored_llm::document::DocumentProcessor;
+use local_vectored_llm::store;
+use std::path::Path;
+use tokio::runtime::Runtime;
+
+/// ベンチマーク用のコレクション ( 終了時に削除する )
+const COLLECTION: &str = "bench";
+
+async fn testdata() -> Vec<Document> {
+    let processor = DocumentProcessor::new(50);
+    let mut documents = Vec::new();
+    for root in ["root1", "root3", "root4"] {
+        let root = Path::new("./testdata").join(root).canonicalize().unwrap();
+        for source in processor.scan(&root).unwrap() {
+            documents.extend(processor.process_file(&root, &source.full_path).await.unwrap().0);
+        }
+    }
+    documents
+}
+
+fn save(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Config::load(&ConfigArgs::default()).unwrap();
+    config.store.path = dir.path().to_path_buf();
+
+    let store = runtime.block_on(store::open(&config)).unwrap();
+    let documents = runtime.block_on(testdata());
+    let collection = COLLECTION.to_string();
+
+    let mut group = c.benchmark_group("save");
+    group.throughput(Throughput::Elements(documents.len() as u64));
+    group.sample_size(10);
+
+    group.bench_function("one_by_one", |b| {
+        b.to_async(&runtime).iter(|| async {
+            for document in &documents {
+                store.save(document, &collection).await.unwrap();
+            }
+        })
+    });
+    group.bench_function("batch", |b| {
+        b.to_async(&runtime).iter(|| async { store.save_batch(&documents, &collection).await.unwrap() })
+    });
+
+    group.finish();
+    runtime.block_on(store.delete_collection(COLLECTION)).unwrap();
+}
+
+criterion_group!(benches, save);
+criterion_main!(benches);
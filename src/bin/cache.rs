@@ -0,0 +1,44 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use local_vectored_llm::config::{Config, ConfigArgs};
+use local_vectored_llm::embedder::{self, CachedEmbedder};
+use local_vectored_llm::{info, store};
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Arg {
+    #[command(subcommand)]
+    command: Command,
+
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 保存済みのチャンクに使われていないベクトルと、他のモデルのキャッシュを削除する
+    Prune,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Arg::parse();
+    let config = Config::load(&args.config)?;
+
+    match args.command {
+        Command::Prune => {
+            let cache = CachedEmbedder::open(&config.embedding.cache_path, embedder::from_config(&config)?)?;
+            let store = store::open(&config).await?;
+
+            let mut contents = vec![];
+            for collection in store.get_collections().await? {
+                contents.extend(store.get_collection_documents(&collection.name).await?.into_iter().map(|d| d.content));
+            }
+
+            let removed = cache.prune(contents.iter().map(|content| content.as_str()))?;
+            info!("Pruned: removed = {}, kept = {}", removed, cache.stats().entries);
+        }
+    }
+
+    Ok(())
+}
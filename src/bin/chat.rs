@@ -1,26 +1,114 @@
 use anyhow::Result;
 use clap::Parser;
-use local_vectored_llm::chroma::store::ChromaStore;
+use local_vectored_llm::chat::{match_collections, Command, Conversation, Turn};
+use local_vectored_llm::config::{Config, ConfigArgs};
 use local_vectored_llm::ollama::OllamaClient;
-use local_vectored_llm::{info};
-use std::io::{self, Write};
+use local_vectored_llm::store::{self, VectorStore};
+use local_vectored_llm::{info, warn};
+use std::io::{self, IsTerminal, Write};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Arg {
-    /// 質問
+    /// 質問 ( 省略すると対話モードになる )
     #[arg(short, long)]
-    question: String,
+    question: Option<String>,
+
+    /// 検索対象のコレクション ( 複数指定可、glob パターン可 e.g. 'health-care-*' )
+    #[arg(long = "collection", value_name = "NAME")]
+    collections: Vec<String>,
+
+    /// 全てのコレクションを検索対象にする
+    #[arg(long, conflicts_with = "collections")]
+    all_collections: bool,
+
+    #[command(flatten)]
+    config: ConfigArgs,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Arg::parse();
-    let chroma = ChromaStore::new().await?;
-    let ollama = OllamaClient::new();
+    let config = Config::load(&args.config)?;
+    let store = store::open(&config).await?;
+    let ollama = OllamaClient::new(&config)?;
 
+    // 標準入力が端末でない場合 ( スクリプトなど ) は入力を求めない
+    let interactive = io::stdin().is_terminal();
+
+    let mut selected_collections = if args.all_collections {
+        store.get_collections().await?.into_iter().map(|collection| collection.name).collect()
+    } else if !args.collections.is_empty() {
+        let collections: Vec<String> =
+            store.get_collections().await?.into_iter().map(|collection| collection.name).collect();
+        match_collections(&collections, &args.collections)?
+    } else if interactive {
+        select_collections(store.as_ref()).await?
+    } else {
+        return Err(anyhow::anyhow!("No collection specified. Use --collection or --all-collections."));
+    };
+    info!("Selected collections: [ {} ]", selected_collections.join(", "));
+    let mut conversation = Conversation::new();
+
+    if let Some(question) = &args.question {
+        answer(store.as_ref(), &ollama, &selected_collections, &mut conversation, question).await?;
+        info!("Complete");
+        return Ok(());
+    }
+
+    if interactive {
+        println!("\nType a question, or /help for commands.");
+    }
+    loop {
+        if interactive {
+            print!("\n> ");
+            io::stdout().flush()?;
+        }
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            break;
+        }
+        let input = input.trim();
+        if input.is_empty() {
+            continue;
+        }
+
+        match Command::parse(input) {
+            None => {
+                if let Err(e) = answer(store.as_ref(), &ollama, &selected_collections, &mut conversation, input).await {
+                    warn!("Failed: {}", e);
+                }
+            }
+            Some(Command::Collections) if !interactive => warn!("/collections requires a terminal"),
+            Some(Command::Collections) => match select_collections(store.as_ref()).await {
+                Ok(collections) => selected_collections = collections,
+                Err(e) => warn!("Failed: {}", e),
+            },
+            Some(Command::Sources) => match conversation.last() {
+                Some(turn) if !turn.sources.is_empty() => turn.sources.iter().for_each(|s| println!("- {}", s)),
+                _ => println!("No sources."),
+            },
+            Some(Command::Reset) => {
+                conversation.reset();
+                info!("Reset conversation");
+            }
+            Some(Command::Save(path)) => match conversation.save(path.as_deref()) {
+                Ok(path) => info!("Saved: {}", path.display()),
+                Err(e) => warn!("Failed: {}", e),
+            },
+            Some(Command::Help) => println!("{}", Command::HELP),
+            Some(Command::Exit) => break,
+            Some(Command::Unknown(name)) => println!("Unknown command: /{}\n{}", name, Command::HELP),
+        }
+    }
+
+    Ok(())
+}
+
+async fn select_collections(store: &dyn VectorStore) -> Result<Vec<String>> {
     // コレクション一覧を取得
-    let collections = chroma.get_collections().await?;
+    let collections = store.get_collections().await?;
 
     // コレクション一覧を表示
     println!();
@@ -36,13 +124,13 @@ async fn main() -> Result<()> {
     io::stdin().read_line(&mut input)?;
 
     // 選択されたコレクション名を取得
-    let selected_collections: Vec<&str> = input
+    let selected_collections: Vec<String> = input
         .trim()
         .split(',')
         .filter_map(|s| {
             s.trim().parse::<usize>().ok().and_then(|n| {
                 if n > 0 && n <= collections.len() {
-                    Some(collections[n - 1].name.as_str())
+                    Some(collections[n - 1].name.clone())
                 } else {
                     None
                 }
@@ -54,20 +142,50 @@ async fn main() -> Result<()> {
         return Err(anyhow::anyhow!("Unexpected collection."));
     }
 
+    Ok(selected_collections)
+}
+
+async fn answer(
+    store: &dyn VectorStore,
+    ollama: &OllamaClient,
+    selected_collections: &[String],
+    conversation: &mut Conversation,
+    question: &str,
+) -> Result<()> {
+    // 会話履歴を踏まえて検索用の質問に書き換える
+    let history = conversation.messages();
+    let query = ollama.rewrite_query(&history, question).await?;
+    if query != question {
+        info!("Rewritten query: {}", query);
+    }
+
+    let selected_collections: Vec<&str> = selected_collections.iter().map(|s| s.as_str()).collect();
     info!("Search context... ( from [ {} ] )", selected_collections.join(", "));
-    let contexts = chroma.search(&args.question, 5, &selected_collections).await?;
+    let hits = store.search(&query, 5, &selected_collections).await?;
     info!(
         "Found {} contexts: [ {} ]",
-        contexts.len(),
-        contexts
-            .iter()
-            .map(|c| format!("{}...", c.chars().take(30).collect::<String>().replace("\n", "")))
+        hits.len(),
+        hits.iter()
+            .map(|h| format!(
+                "{} ( distance = {:.3} ) {}...",
+                h.metadata.citation(),
+                h.distance,
+                h.content.chars().take(30).collect::<String>().replace("\n", "")
+            ))
             .collect::<Vec<_>>()
             .join(", ")
     );
+    let contexts: Vec<String> =
+        hits.iter().map(|h| format!("[出典: {}]\n{}", h.metadata.citation(), h.content)).collect();
     info!("Wait response generation...\n");
-    ollama.streaming_answer(&args.question, &contexts).await?;
+    let answer = ollama.streaming_answer(&history, &query, &contexts).await?;
+    println!();
 
-    info!("Complete");
+    conversation.push(Turn {
+        question: question.to_string(),
+        query,
+        answer,
+        sources: hits.iter().map(|h| h.metadata.citation()).collect(),
+    });
     Ok(())
 }
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use clap::Parser;
-use local_vectored_llm::chroma::store::ChromaStore;
+use local_vectored_llm::config::{Config, ConfigArgs};
+use local_vectored_llm::store;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -8,14 +9,18 @@ struct Arg {
     /// コレクション名
     #[arg(short, long)]
     collection: String,
+
+    #[command(flatten)]
+    config: ConfigArgs,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Arg::parse();
-    let chroma = ChromaStore::new().await?;
+    let config = Config::load(&args.config)?;
+    let store = store::open(&config).await?;
 
-    let documents = chroma.get_collection_documents(&args.collection).await?;
+    let documents = store.get_collection_documents(&args.collection).await?;
 
     for (i, doc) in documents.iter().enumerate() {
         println!("{:<15} | {}", "No.", i + 1);
@@ -24,7 +29,13 @@ async fn main() -> Result<()> {
         println!("{:<15} | {}", "file.path", &doc.metadata.file.path);
         println!("{:<15} | {}", "file.created_at", &doc.metadata.file.created_at);
         println!("{:<15} | {}", "file.updated_at", &doc.metadata.file.updated_at);
+        if let Some(hash) = &doc.metadata.file.hash {
+            println!("{:<15} | {}", "file.hash", hash);
+        }
         println!("{:<15} | {}", "chunk.index", &doc.metadata.chunk.index);
+        for (key, value) in &doc.metadata.chunk.attributes {
+            println!("{:<15} | {}", format!("chunk.{}", key), value);
+        }
         println!("{}-+-{}", "-".repeat(15), "-".repeat(65));
     }
 
@@ -1,11 +1,22 @@
 use anyhow::Result;
-use local_vectored_llm::chroma::store::ChromaStore;
+use clap::Parser;
+use local_vectored_llm::config::{Config, ConfigArgs};
+use local_vectored_llm::store;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Arg {
+    #[command(flatten)]
+    config: ConfigArgs,
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let chroma = ChromaStore::new().await?;
+    let args = Arg::parse();
+    let config = Config::load(&args.config)?;
+    let store = store::open(&config).await?;
 
-    let collections = chroma.get_collections().await?;
+    let collections = store.get_collections().await?;
 
     println!("{:<30} | {:<10}", "name", "data count");
     println!("{}-+-{}", "-".repeat(30), "-".repeat(10));
@@ -1,9 +1,25 @@
 use anyhow::Result;
+use clap::builder::RangedU64ValueParser;
 use clap::Parser;
-use local_vectored_llm::chroma::store::ChromaStore;
-use local_vectored_llm::document::DocumentProcessor;
+use encoding_rs::Encoding;
+use futures::StreamExt;
+use local_vectored_llm::config::{Config, ConfigArgs};
+use local_vectored_llm::document::mail::MailExtractor;
+use local_vectored_llm::document::markdown::MarkdownExtractor;
+use local_vectored_llm::document::table::TableExtractor;
+use local_vectored_llm::document::text::TextExtractor;
+use local_vectored_llm::document::{
+    encoding, filter, CollectionMapper, DocumentProcessor, ExtractorRegistry, FileFilter, SplitterKind,
+};
+use local_vectored_llm::embedder::{self, CachedEmbedder};
+use local_vectored_llm::pipeline::{self, Completed, Pipeline, Task};
+use local_vectored_llm::store;
+use local_vectored_llm::sync::{self, Index, Journal, SyncPlan};
 use local_vectored_llm::{info, warn};
+use std::collections::BTreeSet;
 use std::path::PathBuf;
+use std::pin::pin;
+use std::sync::Arc;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -13,37 +29,215 @@ struct Arg {
     input: PathBuf,
 
     /// チャンクサイズ
-    #[arg(short, long, default_value = "1000")]
+    #[arg(short, long, default_value = "1000", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
     chunk_size: usize,
+
+    /// Markdown の見出しの階層をチャンクの先頭に付与する
+    #[arg(long)]
+    heading_context: bool,
+
+    /// チャンクあたりの最大トークン数 ( 指定するとチャンクサイズの代わりに近似的なトークン数で分割する )
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    max_tokens: Option<usize>,
+
+    /// チャンクの分割方法
+    #[arg(long, value_enum, default_value_t = SplitterKind::Char)]
+    splitter: SplitterKind,
+
+    /// テキスト抽出 ( OCR を含む ) と分割を並行して行うファイル数 ( デフォルトは CPU のコア数 )
+    #[arg(short, long, default_value_t = pipeline::default_extract_jobs())]
+    jobs: usize,
+
+    /// ベクトル化と保存を並行して行うファイル数 ( Ollama への同時リクエスト数 )
+    #[arg(long, default_value_t = pipeline::DEFAULT_EMBED_JOBS)]
+    embed_jobs: usize,
+
+    /// 読み込むファイルの glob パターン ( 複数指定可、対象ディレクトリからの相対パス )
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// 読み込まないファイルやディレクトリの glob パターン ( 複数指定可、対象ディレクトリからの相対パス )
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// 読み込むファイルの最大サイズ ( e.g. 500K, 20M )
+    #[arg(long, value_name = "SIZE", value_parser = filter::parse_size)]
+    max_file_size: Option<u64>,
+
+    /// テキスト、Markdown、CSV / TSV の文字コード ( e.g. shift_jis, euc-jp, utf-16le、指定しない場合は自動判定 )
+    #[arg(long, value_name = "LABEL", value_parser = encoding::parse_label)]
+    encoding: Option<&'static Encoding>,
+
+    /// 隠しファイル ( `.` で始まるファイルやディレクトリ ) も読み込む
+    #[arg(long)]
+    hidden: bool,
+
+    /// .gitignore と .ragignore に従わない
+    #[arg(long)]
+    no_ignore: bool,
+
+    /// 前回の進捗の記録を破棄し、保存済みのファイルも全て読み込み直す
+    #[arg(long)]
+    restart: bool,
+
+    #[command(flatten)]
+    config: ConfigArgs,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Arg::parse();
-    let processor = DocumentProcessor::new(args.chunk_size);
-    let chroma = ChromaStore::new().await?;
+    let config = Config::load(&args.config)?;
+    // 文字コードを指定した場合はテキストと Markdown の Extractor を上書きする
+    let mut registry = ExtractorRegistry::with_attachment_defaults();
+    if args.encoding.is_some() {
+        registry.register(TextExtractor::new(args.encoding)).register(MarkdownExtractor::new(args.encoding));
+    }
+    registry.register(TableExtractor::new(config.table.clone(), args.encoding));
+    // 添付ファイルにもメール以外の上書きした Extractor を使う ( 転送されたメールはメールの Extractor の中で読み込む )
+    let attachments = registry.clone();
+    registry.register(MailExtractor::new(attachments));
+    let processor = DocumentProcessor::new(args.chunk_size)
+        .with_registry(registry)
+        .with_heading_context(args.heading_context)
+        .with_splitter(args.splitter)
+        .with_max_tokens(args.max_tokens)
+        .with_collections(CollectionMapper::new(&config.collection)?)
+        .with_filter(
+            FileFilter::new()
+                .with_include(&args.include)?
+                .with_exclude(&args.exclude)?
+                .with_max_file_size(args.max_file_size)
+                .with_hidden(args.hidden)
+                .with_ignore_files(!args.no_ignore),
+        );
+    // 内容が同じチャンクはベクトル化せずにキャッシュを使う
+    let cache = if config.embedding.cache {
+        Some(Arc::new(CachedEmbedder::open(&config.embedding.cache_path, embedder::from_config(&config)?)?))
+    } else {
+        None
+    };
+    let store = match &cache {
+        Some(cache) => store::open_with(&config, cache.clone()).await?,
+        None => store::open(&config).await?,
+    };
+
+    // 保存済みの情報を取得 ( チャンクの本文は読み込まない )
+    let mut index = Index::new();
+    for collection in store.get_collections().await? {
+        index.extend(store.get_collection_metadatas(&collection.name).await?, &collection.name);
+    }
+    info!("Indexed: {} files", index.len());
+
+    // 中断した場合に続きから再開できるように進捗を記録する
+    let input = args.input.canonicalize()?;
+    let journal_path = Journal::path_for(&Config::path(&args.config));
+    let (sources, skipped) = processor.scan_with_skipped(&input)?;
+    let mut plan = SyncPlan::new(sources, index);
+    let journal = if args.restart {
+        plan.restart();
+        Journal::reset(&journal_path)?
+    } else {
+        let journal = Journal::open(&journal_path)?;
+        journal.resume(&mut plan)?;
+        journal
+    };
 
-    let processed = processor.process_directory(&args.input).await?;
+    // 内容が同じで更新日時だけが変わったファイルは、保存済みの更新日時を書き換える
+    for (source, indexed) in plan.touched() {
+        if let Err(e) = sync::touch(store.as_ref(), source, indexed).await {
+            warn!("Failed to update {}: {}", source.path, e);
+        }
+    }
 
     let mut success_count = 0;
     let mut error_sources = vec![];
+    let mut touched_collections = BTreeSet::new();
+
+    // 追加・変更
+    let tasks: Vec<_> = plan
+        .added
+        .iter()
+        .map(|source| Task { source, indexed: None })
+        .chain(plan.updated.iter().map(|(source, indexed)| Task { source, indexed: Some(indexed) }))
+        .collect();
+    let total = tasks.len();
 
-    for (index, (documents, collection_name)) in processed.iter().enumerate() {
-        for document in documents {
-            match chroma.save(document, collection_name).await {
-                Ok(_) => {
-                    info!("[ {} / {} ] Saved: {}", index + 1, processed.len(), &document.id,);
-                    success_count += 1;
-                }
-                Err(e) => {
-                    warn!("[ {} / {} ] Failed: {}", index + 1, processed.len(), e,);
-                    error_sources.push(&document.metadata.file.path);
-                }
+    let pipeline = Pipeline::new(&processor, store.as_ref())
+        .with_extract_jobs(args.jobs)
+        .with_embed_jobs(args.embed_jobs)
+        .with_context_length(config.ollama.embedding_context_length)
+        .with_journal(&journal);
+    let mut completed = pin!(pipeline.run(&input, tasks));
+    let mut done = 0;
+    while let Some(Completed { task, result }) = completed.next().await {
+        done += 1;
+        if let Some(indexed) = task.indexed {
+            touched_collections.insert(indexed.collection_name.clone());
+        }
+        match result {
+            Ok(chunks) => {
+                info!("[ {} / {} ] Saved: {} ( {} chunks )", done, total, task.source.path, chunks);
+                success_count += chunks;
+            }
+            Err(e) => {
+                warn!("[ {} / {} ] Failed: {}: {}", done, total, task.source.path, e);
+                error_sources.push(&task.source.path);
             }
         }
     }
 
-    info!("Processed: success = {}, failure = {}", success_count, error_sources.len());
+    // 削除
+    let mut deleted_paths = vec![];
+    for (path, indexed) in &plan.deleted {
+        match store.delete(&indexed.ids, &indexed.collection_name).await {
+            Ok(_) => {
+                info!("Deleted: {}", path);
+                touched_collections.insert(indexed.collection_name.clone());
+                deleted_paths.push(input.join(path));
+            }
+            Err(e) => {
+                warn!("Failed: {}", e);
+                error_sources.push(path);
+            }
+        }
+    }
+    if let Err(e) = journal.remove_all(&deleted_paths) {
+        warn!("Failed: {}", e);
+    }
+
+    // 空になったコレクションを削除
+    for collection_name in touched_collections {
+        match store.count(&collection_name).await {
+            Ok(0) => match store.delete_collection(&collection_name).await {
+                Ok(_) => info!("Removed empty collection: {}", collection_name),
+                Err(e) => warn!("Failed: {}", e),
+            },
+            Ok(_) => {}
+            Err(e) => warn!("Failed: {}", e),
+        }
+    }
+
+    info!(
+        "Processed: added = {}, updated = {}, deleted = {}, skipped = {}, saved chunks = {}, failure = {}",
+        plan.added.len(),
+        plan.updated.len(),
+        plan.deleted.len(),
+        plan.skipped.len(),
+        success_count,
+        error_sources.len()
+    );
+    if skipped.total() > 0 {
+        info!(
+            "Filtered: ignored = {}, excluded = {}, hidden = {}, too large = {}, unsupported = {}",
+            skipped.ignored, skipped.excluded, skipped.hidden, skipped.too_large, skipped.unsupported
+        );
+    }
+
+    if let Some(cache) = &cache {
+        let stats = cache.stats();
+        info!("Embedding cache: hits = {}, misses = {}, entries = {}", stats.hits, stats.misses, stats.entries);
+    }
 
     if !error_sources.is_empty() {
         error_sources.into_iter().for_each(|s| warn!("Failed: {}", s))
@@ -0,0 +1,206 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local};
+use globset::Glob;
+use ollama_rs::generation::chat::ChatMessage;
+use std::path::{Path, PathBuf};
+
+/// 回答の生成時にプロンプトに含める直近のやり取りの数
+const MAX_HISTORY_TURNS: usize = 10;
+
+/// 1 回の質問と回答
+#[derive(Debug, Clone)]
+pub struct Turn {
+    pub question: String,
+    /// 検索に使った質問 ( 会話履歴を踏まえて書き換えたもの )
+    pub query: String,
+    pub answer: String,
+    /// 回答に使った参考情報の出典
+    pub sources: Vec<String>,
+}
+
+/// 複数回の質問と回答の履歴
+#[derive(Debug)]
+pub struct Conversation {
+    started_at: DateTime<Local>,
+    turns: Vec<Turn>,
+}
+
+impl Default for Conversation {
+    fn default() -> Self {
+        Self { started_at: Local::now(), turns: vec![] }
+    }
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, turn: Turn) {
+        self.turns.push(turn);
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.turns.is_empty()
+    }
+
+    pub fn last(&self) -> Option<&Turn> {
+        self.turns.last()
+    }
+
+    /// Ollama の chat endpoint に渡す直近のやり取り
+    pub fn messages(&self) -> Vec<ChatMessage> {
+        self.turns
+            .iter()
+            .skip(self.turns.len().saturating_sub(MAX_HISTORY_TURNS))
+            .flat_map(|turn| [ChatMessage::user(turn.question.clone()), ChatMessage::assistant(turn.answer.clone())])
+            .collect()
+    }
+
+    /// Markdown 形式で保存する ( パスの指定がない場合はカレントディレクトリに日時のファイル名で保存する )
+    pub fn save(&self, path: Option<&Path>) -> Result<PathBuf> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => PathBuf::from(format!("chat-{}.md", self.started_at.format("%Y%m%d-%H%M%S"))),
+        };
+        std::fs::write(&path, self.to_markdown())?;
+        Ok(path)
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut markdown = format!("# Chat ( {} )\n", self.started_at.format("%Y-%m-%d %H:%M:%S"));
+        for (i, turn) in self.turns.iter().enumerate() {
+            markdown.push_str(&format!("\n## Q{}\n\n{}\n", i + 1, turn.question));
+            if turn.query != turn.question {
+                markdown.push_str(&format!("\n検索: {}\n", turn.query));
+            }
+            markdown.push_str(&format!("\n### Answer\n\n{}\n", turn.answer.trim()));
+            if !turn.sources.is_empty() {
+                markdown.push_str("\n### Sources\n\n");
+                turn.sources.iter().for_each(|source| markdown.push_str(&format!("- {}\n", source)));
+            }
+        }
+        markdown
+    }
+}
+
+/// パターン ( glob ) に一致するコレクション名 ( 1 つも一致しないパターンがある場合はエラー )
+pub fn match_collections(collections: &[String], patterns: &[String]) -> Result<Vec<String>> {
+    let mut selected: Vec<String> = Vec::new();
+    for pattern in patterns {
+        let matcher = Glob::new(pattern)?.compile_matcher();
+        let matched: Vec<_> = collections.iter().filter(|collection| matcher.is_match(collection.as_str())).collect();
+        if matched.is_empty() {
+            return Err(anyhow!("No collection matches: {}", pattern));
+        }
+        for collection in matched {
+            if !selected.contains(collection) {
+                selected.push(collection.clone());
+            }
+        }
+    }
+    Ok(selected)
+}
+
+/// REPL のスラッシュコマンド
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// 検索対象のコレクションを選び直す
+    Collections,
+    /// 直前の回答の出典を表示する
+    Sources,
+    /// 会話履歴を消去する
+    Reset,
+    /// 会話履歴をファイルに保存する
+    Save(Option<PathBuf>),
+    Help,
+    Exit,
+    Unknown(String),
+}
+
+impl Command {
+    pub const HELP: &'static str = "/collections  select collections\n/sources      show sources of the last answer\n/reset        clear conversation history\n/save [path]  save conversation as markdown\n/help         show this help\n/exit         exit";
+
+    /// `/` で始まる入力のみコマンドとして解釈する
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim().strip_prefix('/')?;
+        let (name, arg) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+        let arg = arg.trim();
+
+        Some(match name {
+            "collections" => Command::Collections,
+            "sources" => Command::Sources,
+            "reset" => Command::Reset,
+            "save" => Command::Save((!arg.is_empty()).then(|| PathBuf::from(arg))),
+            "help" => Command::Help,
+            "exit" | "quit" => Command::Exit,
+            _ => Command::Unknown(name.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ollama_rs::generation::chat::MessageRole;
+
+    #[test]
+    fn command() {
+        assert_eq!(Command::parse("質問"), None);
+        assert_eq!(Command::parse("/reset"), Some(Command::Reset));
+        assert_eq!(Command::parse(" /save  out/chat.md "), Some(Command::Save(Some(PathBuf::from("out/chat.md")))));
+        assert_eq!(Command::parse("/save"), Some(Command::Save(None)));
+        assert_eq!(Command::parse("/foo bar"), Some(Command::Unknown("foo".to_string())));
+    }
+
+    #[test]
+    fn collections() {
+        let collections: Vec<String> =
+            ["health-care-api-spec", "health-care-db-spec", "root"].iter().map(|s| s.to_string()).collect();
+        let patterns = |patterns: &[&str]| patterns.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            match_collections(&collections, &patterns(&["health-care-*", "health-care-db-spec"])).unwrap(),
+            vec!["health-care-api-spec", "health-care-db-spec"]
+        );
+        assert_eq!(match_collections(&collections, &patterns(&["root"])).unwrap(), vec!["root"]);
+        assert!(match_collections(&collections, &patterns(&["test"])).is_err());
+    }
+
+    #[test]
+    fn history() {
+        let mut conversation = Conversation::new();
+        for i in 0..MAX_HISTORY_TURNS + 1 {
+            conversation.push(turn(&format!("質問{}", i)));
+        }
+
+        let messages = conversation.messages();
+
+        assert_eq!(messages.len(), MAX_HISTORY_TURNS * 2);
+        assert_eq!(messages[0].role, MessageRole::User);
+        assert_eq!(messages[0].content, "質問1");
+        assert_eq!(messages[1].role, MessageRole::Assistant);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = conversation.save(Some(&dir.path().join("chat.md"))).unwrap();
+        let markdown = std::fs::read_to_string(path).unwrap();
+        assert!(markdown.contains("## Q11\n\n質問10\n"));
+        assert!(markdown.contains("- a.txt ( chunk 0 )\n"));
+
+        conversation.reset();
+        assert!(conversation.is_empty());
+    }
+
+    fn turn(question: &str) -> Turn {
+        Turn {
+            question: question.to_string(),
+            query: question.to_string(),
+            answer: "回答".to_string(),
+            sources: vec!["a.txt ( chunk 0 )".to_string()],
+        }
+    }
+}
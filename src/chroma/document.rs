@@ -1,9 +1,13 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
 
 pub type CollectionName = String;
 
+/// 抽出元の構造に応じた任意のメタデータ ( 配列やオブジェクトは保存時に JSON の文字列にする )
+pub type Attributes = BTreeMap<String, Value>;
+
 #[derive(Debug)]
 pub struct Document {
     pub id: String,
@@ -20,7 +24,7 @@ pub struct Metadata {
 
 impl Metadata {
     pub fn to_map(&self) -> Map<String, Value> {
-        json!({
+        let mut map = json!({
             "file_path": self.file.path,
             "file_created_at": self.file.created_at.timestamp(),
             "file_updated_at": self.file.updated_at.timestamp(),
@@ -28,7 +32,41 @@ impl Metadata {
         })
         .as_object()
         .unwrap()
-        .clone()
+        .clone();
+        if let Some(hash) = &self.file.hash {
+            map.insert("file_hash".to_string(), json!(hash));
+        }
+        // 表の列名などが既存のキーと重なる場合は既存のキーを優先する
+        for (key, value) in &self.chunk.attributes {
+            let value = match value {
+                Value::Null => continue,
+                Value::Array(_) | Value::Object(_) => json!(value.to_string()),
+                _ => value.clone(),
+            };
+            map.entry(format!("chunk_{}", key)).or_insert(value);
+        }
+        map
+    }
+
+    /// 回答の出典として示す文字列
+    pub fn citation(&self) -> String {
+        let attributes = &self.chunk.attributes;
+        let location = if let Some(page) = attributes.get("page") {
+            format!(" p.{}", page)
+        } else if let Some(slide) = attributes.get("slide") {
+            format!(" slide {}", slide)
+        } else if let Some(sheet) = attributes.get("sheet").and_then(|sheet| sheet.as_str()) {
+            format!(" sheet {}", sheet)
+        } else if let Some(row) = attributes.get("row") {
+            format!(" row {}", row)
+        } else if let (Some(from), Some(date)) = (attributes.get("from"), attributes.get("date")) {
+            format!(" {} {}", date.as_str().unwrap_or_default(), from.as_str().unwrap_or_default())
+        } else if let (Some(start), Some(end)) = (attributes.get("line_start"), attributes.get("line_end")) {
+            format!(" L{}-{}", start, end)
+        } else {
+            String::new()
+        };
+        format!("{}{} ( chunk {} )", self.file.path, location, self.chunk.index)
     }
 
     pub fn from_map(map: Map<String, Value>) -> Self {
@@ -37,8 +75,16 @@ impl Metadata {
                 path: map.get("file_path").unwrap().as_str().unwrap().to_string(),
                 created_at: DateTime::from_timestamp(map.get("file_created_at").unwrap().as_i64().unwrap(), 0).unwrap(),
                 updated_at: DateTime::from_timestamp(map.get("file_updated_at").unwrap().as_i64().unwrap(), 0).unwrap(),
+                hash: map.get("file_hash").and_then(|hash| hash.as_str()).map(|hash| hash.to_string()),
+            },
+            chunk: ChunkMetadata {
+                index: map.get("chunk_index").unwrap().as_u64().unwrap() as usize,
+                attributes: map
+                    .iter()
+                    .filter_map(|(key, value)| Some((key.strip_prefix("chunk_")?.to_string(), value.clone())))
+                    .filter(|(key, _)| key != "index")
+                    .collect(),
             },
-            chunk: ChunkMetadata { index: map.get("chunk_index").unwrap().as_u64().unwrap() as usize },
             search: SearchMetadata {},
         }
     }
@@ -51,14 +97,54 @@ pub struct FileMetadata {
     pub created_at: DateTime<Utc>,
     #[serde(with = "chrono::serde::ts_seconds")]
     pub updated_at: DateTime<Utc>,
+    /// ファイルの内容の SHA-256 ( 16 進数 )
+    #[serde(default)]
+    pub hash: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ChunkMetadata {
     pub index: usize,
+    /// ページ番号など、抽出元の構造に応じたメタデータ
+    #[serde(default)]
+    pub attributes: Attributes,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchMetadata {
     // 今後の拡張性のため
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_map() {
+        let metadata = Metadata {
+            file: FileMetadata {
+                path: "a.csv".to_string(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                hash: None,
+            },
+            chunk: ChunkMetadata {
+                index: 1,
+                attributes: Attributes::from([
+                    ("index".to_string(), json!("x")),
+                    ("row".to_string(), json!(3)),
+                    ("tags".to_string(), json!(["a", "b"])),
+                    ("closed".to_string(), Value::Null),
+                ]),
+            },
+            search: SearchMetadata {},
+        };
+
+        let map = metadata.to_map();
+        assert_eq!(map.get("chunk_index"), Some(&json!(1)));
+        assert_eq!(map.get("chunk_row"), Some(&json!(3)));
+        assert_eq!(map.get("chunk_tags"), Some(&json!(r#"["a","b"]"#)));
+        assert!(!map.contains_key("chunk_closed"));
+        assert_eq!(metadata.citation(), "a.csv row 3 ( chunk 1 )");
+    }
+}
@@ -1,30 +1,50 @@
 use crate::chroma::document::{CollectionName, Document, Metadata};
+use crate::config::Config;
+use crate::embedder::Embedder;
+use crate::store::{merge_hits, CollectionInfo, SearchHit, VectorStore};
 use anyhow::Result;
+use async_trait::async_trait;
 use chromadb::client::ChromaClient;
 use chromadb::client::ChromaClientOptions;
-use chromadb::collection::{CollectionEntries, QueryOptions};
-use ollama_rs::generation::embeddings::request::{EmbeddingsInput, GenerateEmbeddingsRequest};
-use ollama_rs::Ollama;
+use chromadb::collection::{ChromaCollection, CollectionEntries, GetOptions, QueryOptions};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 pub struct ChromaStore {
     client: ChromaClient,
-    ollama: Ollama,
-}
-
-#[derive(Debug)]
-pub struct CollectionInfo {
-    pub name: String,
-    pub count: usize,
+    embedder: Arc<dyn Embedder>,
+    batch_size: usize,
+    /// 取得済みのコレクション ( 保存のたびに取得しない )
+    collections: Mutex<HashMap<CollectionName, Arc<ChromaCollection>>>,
 }
 
 impl ChromaStore {
-    pub async fn new() -> Result<Self> {
-        let options = ChromaClientOptions { url: Some("http://localhost:18888".to_string()), ..Default::default() };
+    pub async fn new(config: &Config, embedder: Arc<dyn Embedder>) -> Result<Self> {
+        let options = ChromaClientOptions { url: Some(config.chroma.url.clone()), ..Default::default() };
         let client = ChromaClient::new(options).await?;
-        Ok(Self { client, ollama: Ollama::new("http://localhost", 11434) })
+        Ok(Self { client, embedder, batch_size: config.store.batch_size.max(1), collections: Mutex::default() })
+    }
+
+    async fn collection(&self, collection_name: &str, create: bool) -> Result<Arc<ChromaCollection>> {
+        if let Some(collection) = self.collections.lock().unwrap().get(collection_name) {
+            return Ok(collection.clone());
+        }
+
+        let collection = if create {
+            self.client.get_or_create_collection(collection_name, None).await?
+        } else {
+            self.client.get_collection(collection_name).await?
+        };
+        let collection = Arc::new(collection);
+        self.collections.lock().unwrap().insert(collection_name.to_string(), collection.clone());
+        Ok(collection)
     }
+}
 
-    pub async fn get_collections(&self) -> Result<Vec<CollectionInfo>> {
+#[async_trait]
+impl VectorStore for ChromaStore {
+    async fn get_collections(&self) -> Result<Vec<CollectionInfo>> {
         let collections = self.client.list_collections().await?;
         let mut result = Vec::new();
 
@@ -37,8 +57,8 @@ impl ChromaStore {
         Ok(result)
     }
 
-    pub async fn get_collection_documents(&self, collection_name: &str) -> Result<Vec<Document>> {
-        let collection = self.client.get_collection(collection_name).await?;
+    async fn get_collection_documents(&self, collection_name: &str) -> Result<Vec<Document>> {
+        let collection = self.collection(collection_name, false).await?;
         let count = collection.count().await?;
         let results = collection.peek(count).await?;
 
@@ -56,57 +76,112 @@ impl ChromaStore {
         Ok(result)
     }
 
-    pub async fn save(&self, document: &Document, collection_name: &CollectionName) -> Result<()> {
-        let embedding = self.generate_embedding(&document.content).await?;
+    async fn get_collection_metadatas(&self, collection_name: &str) -> Result<Vec<(String, Metadata)>> {
+        let collection = self.collection(collection_name, false).await?;
+        let options = GetOptions { include: Some(vec!["metadatas".to_string()]), ..Default::default() };
+        let results = collection.get(options).await?;
 
-        let collection = self.client.get_or_create_collection(collection_name, None).await?;
+        let metadatas = results.metadatas.unwrap_or_default();
+        Ok(results
+            .ids
+            .into_iter()
+            .zip(metadatas)
+            .filter_map(|(id, metadata_map)| Some((id, Metadata::from_map(metadata_map?))))
+            .collect())
+    }
+
+    async fn save_batch(&self, documents: &[Document], collection_name: &CollectionName) -> Result<()> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+        let collection = self.collection(collection_name, true).await?;
 
+        for batch in documents.chunks(self.batch_size) {
+            let contents: Vec<String> = batch.iter().map(|document| document.content.clone()).collect();
+            let embeddings = self.embedder.embed_batch(&contents).await?;
+
+            let entries = CollectionEntries {
+                ids: batch.iter().map(|document| document.id.as_str()).collect(),
+                metadatas: Some(batch.iter().map(|document| document.metadata.to_map()).collect()),
+                documents: Some(contents.iter().map(|content| content.as_str()).collect()),
+                embeddings: Some(embeddings),
+            };
+            collection.upsert(entries, None).await?;
+        }
+        Ok(())
+    }
+
+    async fn update_metadata(
+        &self,
+        ids: &[String],
+        metadata: &Map<String, Value>,
+        collection_name: &str,
+    ) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let collection = self.collection(collection_name, false).await?;
+        // Chroma の update は指定したキーのみを上書きする
         let entries = CollectionEntries {
-            ids: vec![&document.id],
-            metadatas: Some(vec![document.metadata.to_map()]),
-            documents: Some(vec![&document.content]),
-            embeddings: Some(vec![embedding]),
+            ids: ids.iter().map(|id| id.as_str()).collect(),
+            metadatas: Some(vec![metadata.clone(); ids.len()]),
+            documents: None,
+            embeddings: None,
         };
+        collection.update(entries, None).await
+    }
 
-        collection.add(entries, None).await?;
+    async fn delete(&self, ids: &[String], collection_name: &str) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let collection = self.collection(collection_name, false).await?;
+        collection.delete(Some(ids.iter().map(|id| id.as_str()).collect()), None, None).await?;
         Ok(())
     }
 
-    pub async fn search(&self, query: &str, limit: usize, collection_names: &[&str]) -> Result<Vec<String>> {
-        let query_embedding = self.generate_embedding(query).await?;
-        let mut all_results = Vec::new();
+    async fn delete_collection(&self, collection_name: &str) -> Result<()> {
+        self.collections.lock().unwrap().remove(collection_name);
+        self.client.delete_collection(collection_name).await
+    }
+
+    async fn count(&self, collection_name: &str) -> Result<usize> {
+        let collection = self.collection(collection_name, false).await?;
+        collection.count().await
+    }
+
+    async fn search(&self, query: &str, limit: usize, collection_names: &[&str]) -> Result<Vec<SearchHit>> {
+        let query_embedding = self.embedder.embed(query).await?;
+        let mut all_hits = Vec::new();
 
         for collection_name in collection_names {
-            let collection = self.client.get_collection(collection_name).await?;
+            let collection = self.collection(collection_name, false).await?;
             let options = QueryOptions {
                 query_embeddings: Some(vec![query_embedding.clone()]),
                 n_results: Some(limit),
+                include: Some(vec!["documents", "metadatas", "distances"]),
                 ..Default::default()
             };
             let results = collection.query(options, None).await?;
-            if let Some(docs) = results.documents {
-                all_results.extend(docs.into_iter().flatten());
-            }
-        }
-
-        // 結果を重複排除して返す
-        all_results.sort();
-        all_results.dedup();
 
-        // 指定されたlimitを超えないように調整
-        if all_results.len() > limit {
-            all_results.truncate(limit);
+            // 1 クエリ分の結果のみ
+            let ids = results.ids.into_iter().next().unwrap_or_default();
+            let documents = results.documents.and_then(|d| d.into_iter().next()).unwrap_or_default();
+            let metadatas = results.metadatas.and_then(|m| m.into_iter().next()).unwrap_or_default();
+            let distances = results.distances.and_then(|d| d.into_iter().next()).unwrap_or_default();
+
+            for (i, id) in ids.into_iter().enumerate() {
+                let Some(metadata_map) = metadatas.get(i).and_then(|m| m.clone()) else { continue };
+                all_hits.push(SearchHit {
+                    id,
+                    content: documents.get(i).cloned().unwrap_or_default(),
+                    metadata: Metadata::from_map(metadata_map),
+                    distance: distances.get(i).copied().unwrap_or(f32::MAX),
+                    collection: collection_name.to_string(),
+                });
+            }
         }
 
-        Ok(all_results)
-    }
-
-    async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>> {
-        let req = GenerateEmbeddingsRequest::new(
-            "7shi/ezo-gemma-2-jpn:2b-instruct-q8_0".to_string(),
-            EmbeddingsInput::Single(text.to_string()),
-        );
-        let result = self.ollama.generate_embeddings(req).await?;
-        Ok(result.embeddings.into_iter().next().unwrap_or_default())
+        Ok(merge_hits(all_hits, limit))
     }
 }
@@ -0,0 +1,418 @@
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use ollama_rs::models::ModelOptions;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// 設定ファイルのデフォルトのパス ( カレントディレクトリ )
+pub const CONFIG_FILE: &str = "local-vectored-llm.toml";
+
+/// 設定ファイルのパスを指定する環境変数
+pub const CONFIG_ENV: &str = "LVL_CONFIG";
+
+/// 設定値を上書きする環境変数の接頭辞 ( e.g. LVL_OLLAMA_CHAT_MODEL )
+pub const ENV_PREFIX: &str = "LVL_";
+
+/// 全ての実行ファイルで共通の設定
+///
+/// 優先順位は CLI 引数 > 環境変数 > 設定ファイル > デフォルト値
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub store: StoreConfig,
+    pub collection: CollectionConfig,
+    pub chroma: ChromaConfig,
+    pub ollama: OllamaConfig,
+    pub embedding: EmbeddingConfig,
+    pub generation: GenerationConfig,
+    pub table: TableConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoreConfig {
+    pub backend: StoreBackend,
+    /// `local` の保存先のディレクトリ
+    pub path: PathBuf,
+    /// まとめてベクトル化して保存するチャンクの数
+    pub batch_size: usize,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        Self { backend: StoreBackend::Chroma, path: PathBuf::from("local-vectored-llm-store"), batch_size: 32 }
+    }
+}
+
+/// チャンクとベクトルの保存先
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreBackend {
+    /// Chroma DB ( `chroma.url` )
+    Chroma,
+    /// ローカルのディレクトリ ( `store.path` )
+    Local,
+}
+
+/// ファイルのパスからコレクション名を決める規則
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectionConfig {
+    /// コレクション名に使うディレクトリの階層数 ( 0 で全体を 1 つのコレクションにする )
+    pub depth: usize,
+    /// 直下のファイル ( depth = 0 の場合は全てのファイル ) のコレクション名
+    pub root: String,
+    /// 階層より優先するパターンごとのコレクション名 ( 先に書いたものを優先 )
+    pub rules: Vec<CollectionRule>,
+}
+
+impl Default for CollectionConfig {
+    fn default() -> Self {
+        Self { depth: 2, root: "root".to_string(), rules: vec![] }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CollectionRule {
+    /// 対象ディレクトリからの相対パスの glob パターン ( e.g. `docs/**/*.md` )
+    pub pattern: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChromaConfig {
+    pub url: String,
+}
+
+impl Default for ChromaConfig {
+    fn default() -> Self {
+        Self { url: "http://localhost:18888".to_string() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OllamaConfig {
+    pub url: String,
+    /// 回答の生成に使うモデル
+    pub chat_model: String,
+    /// ベクトル化に使うモデル
+    pub embedding_model: String,
+    /// ベクトル化に使うモデルのコンテキスト長 ( トークン数、これを超えるチャンクは Ollama で切り詰められる )
+    pub embedding_context_length: usize,
+}
+
+impl Default for OllamaConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:11434".to_string(),
+            chat_model: "7shi/ezo-gemma-2-jpn:2b-instruct-q8_0".to_string(),
+            embedding_model: "7shi/ezo-gemma-2-jpn:2b-instruct-q8_0".to_string(),
+            embedding_context_length: 2048,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmbeddingConfig {
+    pub backend: EmbeddingBackend,
+    /// `hash` のベクトルの次元数
+    pub dimensions: usize,
+    /// `load` でチャンクのベクトルをキャッシュする ( 内容が同じチャンクはベクトル化しない )
+    pub cache: bool,
+    /// キャッシュの保存先のディレクトリ
+    pub cache_path: PathBuf,
+}
+
+impl Default for EmbeddingConfig {
+    fn default() -> Self {
+        Self {
+            backend: EmbeddingBackend::Ollama,
+            dimensions: 256,
+            cache: true,
+            cache_path: PathBuf::from("local-vectored-llm-cache"),
+        }
+    }
+}
+
+/// ベクトル化の方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingBackend {
+    /// Ollama の埋め込みモデル ( `ollama.embedding_model` )
+    Ollama,
+    /// 文字 n-gram のハッシュ ( オフラインで動く決定的な方法、テスト用 )
+    Hash,
+}
+
+/// 回答生成のオプション
+///
+/// デフォルト値は生成速度と品質のバランスを考慮した値
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenerationConfig {
+    // 生成速度の最適化
+    /// 高 → より創造的な出力になる, 低 → より決定論的な出力になる, default: 0
+    pub mirostat: u8,
+    /// 高 → 並列処理が増えて高速化, 低 → シングルスレッドで低速, default: 自動検出
+    pub num_thread: u32,
+    /// 高 → より長い文章を生成, 低 → より短い文章を生成, default: 128
+    pub num_predict: i32,
+    /// 高 → 低確率トークンの影響を強く抑制, 低 → 低確率トークンも許容, default: 1.0
+    pub tfs_z: f32,
+
+    // 出力の決定論性向上
+    /// 高 → より創造的で多様な出力, 低 → より決定論的で一貫性のある出力, default: 0.8
+    pub temperature: f32,
+    /// 高 → より多様な単語を選択, 低 → より確実な単語を選択, default: 40
+    pub top_k: u32,
+    /// 高 → より多様な文章を生成, 低 → より確実な文章を生成, default: 0.9
+    pub top_p: f32,
+    /// 固定値 → 同じ入力に対して同じ出力を生成, 0 → ランダム, default: 0
+    pub seed: i32,
+
+    // ハードウェア最適化
+    /// 高 → より多くのGPUを使用, 低 → より少ないGPUを使用, default: macOS = 1, 他 = 0
+    pub num_gpu: u32,
+
+    // 繰り返し制御
+    /// 高 → より広い範囲で繰り返しを防止, 低 → より狭い範囲で繰り返しを防止, default: 64
+    pub repeat_last_n: i32,
+    /// 高 → より強く繰り返しを抑制, 低 → より緩やかに繰り返しを抑制, default: 1.1
+    pub repeat_penalty: f32,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            mirostat: 0,
+            num_thread: 4,
+            num_predict: 128,
+            tfs_z: 1.0,
+            temperature: 0.5,
+            top_k: 20,
+            top_p: 0.7,
+            seed: 42,
+            num_gpu: 0,
+            repeat_last_n: 64,
+            repeat_penalty: 1.1,
+        }
+    }
+}
+
+impl GenerationConfig {
+    pub fn to_model_options(&self) -> ModelOptions {
+        ModelOptions::default()
+            .mirostat(self.mirostat)
+            .num_thread(self.num_thread)
+            .num_predict(self.num_predict)
+            .tfs_z(self.tfs_z)
+            .temperature(self.temperature)
+            .top_k(self.top_k)
+            .top_p(self.top_p)
+            .seed(self.seed)
+            .num_gpu(self.num_gpu)
+            .repeat_last_n(self.repeat_last_n)
+            .repeat_penalty(self.repeat_penalty)
+    }
+}
+
+/// CSV / TSV / JSON / JSON Lines の読み込み方
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TableConfig {
+    pub mode: TableMode,
+    /// `row` で本文にする列 ( 空の場合は全ての列、それ以外の列はチャンクのメタデータにする )
+    pub text_columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableMode {
+    /// ファイル全体を 1 つの文書にする ( 1 行 ( レコード ) を `#行番号 列: 値 / 列: 値` の 1 行にまとめる )
+    #[default]
+    File,
+    /// 1 行 ( レコード ) ごとに 1 つの文書にする
+    Row,
+}
+
+// 全ての実行ファイルで共通の設定用 CLI 引数
+#[derive(Args, Debug, Default)]
+pub struct ConfigArgs {
+    /// 設定ファイルのパス
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Chroma DB の URL
+    #[arg(long, global = true)]
+    pub chroma_url: Option<String>,
+
+    /// Ollama の URL
+    #[arg(long, global = true)]
+    pub ollama_url: Option<String>,
+
+    /// 回答の生成に使うモデル
+    #[arg(long, global = true)]
+    pub chat_model: Option<String>,
+
+    /// ベクトル化に使うモデル
+    #[arg(long, global = true)]
+    pub embedding_model: Option<String>,
+
+    /// 任意の設定値の上書き ( e.g. --set generation.temperature=0.2 )
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    pub overrides: Vec<String>,
+}
+
+impl Config {
+    pub fn load(args: &ConfigArgs) -> Result<Self> {
+        // デフォルトのパスの設定ファイルは存在しなくてもよい
+        let path = Self::path(args);
+        let mut table =
+            if path.exists() || path != Path::new(CONFIG_FILE) { Self::read_table(&path)? } else { Table::new() };
+
+        let mut config: Config = table.clone().try_into()?;
+        table = Table::try_from(&config)?;
+
+        apply_env(&mut table, |key| std::env::var(key).ok())?;
+        apply_args(&mut table, args)?;
+
+        config = table.try_into()?;
+        Ok(config)
+    }
+
+    /// 設定ファイルのパス ( 設定ファイルを使っていない場合はカレントディレクトリのデフォルトのパス )
+    pub fn path(args: &ConfigArgs) -> PathBuf {
+        args.config
+            .clone()
+            .or_else(|| std::env::var_os(CONFIG_ENV).map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from(CONFIG_FILE))
+    }
+
+    fn read_table(path: &Path) -> Result<Table> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        content.parse::<Table>().with_context(|| format!("Failed to parse {}", path.display()))
+    }
+}
+
+fn apply_env<F: Fn(&str) -> Option<String>>(table: &mut Table, env: F) -> Result<()> {
+    for (section, values) in table.iter_mut() {
+        let Some(values) = values.as_table_mut() else { continue };
+        for (key, value) in values.iter_mut() {
+            let name = format!("{}{}_{}", ENV_PREFIX, section, key).to_uppercase();
+            if let Some(raw) = env(&name) {
+                *value = parse_as(value, &raw).with_context(|| format!("Invalid value of {}", name))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn apply_args(table: &mut Table, args: &ConfigArgs) -> Result<()> {
+    let mut overrides = vec![];
+    if let Some(url) = &args.chroma_url {
+        overrides.push(("chroma.url".to_string(), url.clone()));
+    }
+    if let Some(url) = &args.ollama_url {
+        overrides.push(("ollama.url".to_string(), url.clone()));
+    }
+    if let Some(model) = &args.chat_model {
+        overrides.push(("ollama.chat_model".to_string(), model.clone()));
+    }
+    if let Some(model) = &args.embedding_model {
+        overrides.push(("ollama.embedding_model".to_string(), model.clone()));
+    }
+    for item in &args.overrides {
+        let (key, raw) = item.split_once('=').ok_or_else(|| anyhow!("Invalid override: {}", item))?;
+        overrides.push((key.trim().to_string(), raw.trim().to_string()));
+    }
+
+    for (key, raw) in overrides {
+        let value = key
+            .split_once('.')
+            .and_then(|(section, name)| table.get_mut(section)?.as_table_mut()?.get_mut(name))
+            .ok_or_else(|| anyhow!("Unknown config key: {}", key))?;
+        *value = parse_as(value, &raw).with_context(|| format!("Invalid value of {}", key))?;
+    }
+    Ok(())
+}
+
+/// 既存の値の型に合わせて文字列を変換する
+fn parse_as(current: &Value, raw: &str) -> Result<Value> {
+    Ok(match current {
+        Value::String(_) => Value::String(raw.to_string()),
+        Value::Integer(_) => Value::Integer(raw.parse()?),
+        Value::Float(_) => Value::Float(raw.parse()?),
+        Value::Boolean(_) => Value::Boolean(raw.parse()?),
+        _ => format!("v = {}", raw).parse::<Table>()?.remove("v").unwrap(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precedence() {
+        let mut table: Table = "[ollama]\nchat_model = \"file\"\nembedding_model = \"file\"\n".parse().unwrap();
+        let config: Config = table.clone().try_into().unwrap();
+        table = Table::try_from(&config).unwrap();
+
+        apply_env(&mut table, |key| match key {
+            "LVL_OLLAMA_CHAT_MODEL" => Some("env".to_string()),
+            "LVL_OLLAMA_EMBEDDING_MODEL" => Some("env".to_string()),
+            "LVL_GENERATION_NUM_PREDICT" => Some("256".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        let args = ConfigArgs {
+            chat_model: Some("cli".to_string()),
+            overrides: vec!["generation.temperature=0.2".to_string()],
+            ..Default::default()
+        };
+        apply_args(&mut table, &args).unwrap();
+
+        let config: Config = table.try_into().unwrap();
+        assert_eq!(config.ollama.chat_model, "cli");
+        assert_eq!(config.ollama.embedding_model, "env");
+        assert_eq!(config.ollama.url, "http://localhost:11434");
+        assert_eq!(config.generation.num_predict, 256);
+        assert_eq!(config.generation.temperature, 0.2);
+    }
+
+    #[test]
+    fn store_backend() {
+        let mut table = Table::try_from(Config::default()).unwrap();
+
+        apply_env(&mut table, |key| (key == "LVL_STORE_BACKEND").then(|| "local".to_string())).unwrap();
+
+        let config: Config = table.try_into().unwrap();
+        assert_eq!(config.store.backend, StoreBackend::Local);
+    }
+
+    #[test]
+    fn unknown_key() {
+        let mut table = Table::try_from(Config::default()).unwrap();
+        let args = ConfigArgs { overrides: vec!["generation.unknown=1".to_string()], ..Default::default() };
+        assert!(apply_args(&mut table, &args).is_err());
+    }
+
+    #[test]
+    fn table() {
+        let mut table = Table::try_from(Config::default()).unwrap();
+        apply_env(&mut table, |key| (key == "LVL_TABLE_MODE").then(|| "row".to_string())).unwrap();
+        let args = ConfigArgs {
+            overrides: vec![r#"table.text_columns=["question", "answer"]"#.to_string()],
+            ..Default::default()
+        };
+        apply_args(&mut table, &args).unwrap();
+
+        let config: Config = table.try_into().unwrap();
+        assert_eq!(config.table.mode, TableMode::Row);
+        assert_eq!(config.table.text_columns, vec!["question", "answer"]);
+    }
+}
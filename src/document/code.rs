@@ -0,0 +1,650 @@
+use crate::chroma::document::Attributes;
+use crate::document::encoding;
+use crate::document::extractor::{Block, ExtractedDocument, Extractor, Section};
+use anyhow::Result;
+use serde_json::json;
+use std::ops::Range;
+use std::path::Path;
+
+/// 続けて 1 つの区切りにまとめる小さな定義の合計行数
+const MERGE_LINES: usize = 30;
+
+/// 定義の境界を判定できない言語で 1 つの区切りにする行数
+const FALLBACK_LINES: usize = 40;
+
+/// 定義の境界を判定できない言語の拡張子と言語名 ( 行数で区切る )
+const FALLBACK_LANGUAGES: [(&str, &str); 16] = [
+    ("c", "c"),
+    ("h", "c"),
+    ("cc", "cpp"),
+    ("cpp", "cpp"),
+    ("hpp", "cpp"),
+    ("cs", "csharp"),
+    ("kt", "kotlin"),
+    ("swift", "swift"),
+    ("scala", "scala"),
+    ("rb", "ruby"),
+    ("php", "php"),
+    ("lua", "lua"),
+    ("sh", "shell"),
+    ("bash", "shell"),
+    ("sql", "sql"),
+    ("r", "r"),
+];
+
+const EXTENSIONS: [&str; 26] = [
+    "rs", "ts", "tsx", "mts", "js", "jsx", "mjs", "py", "go", "java", "c", "h", "cc", "cpp", "hpp", "cs", "kt",
+    "swift", "scala", "rb", "php", "lua", "sh", "bash", "sql", "r",
+];
+
+/// ソースコード
+///
+/// 関数やクラスなどのトップレベルの定義 ( クラスや impl の中はメソッド ) ごとに区切り、
+/// 言語 ( `language` )、定義の名前 ( `symbol` )、行の範囲 ( `line_start`, `line_end` ) をメタデータに記録する。
+/// 小さな定義は続けて 1 つの区切りにまとめる
+pub struct CodeExtractor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    TypeScript,
+    JavaScript,
+    Python,
+    Go,
+    Java,
+}
+
+impl Language {
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "rs" => Some(Language::Rust),
+            "ts" | "tsx" | "mts" => Some(Language::TypeScript),
+            "js" | "jsx" | "mjs" => Some(Language::JavaScript),
+            "py" => Some(Language::Python),
+            "go" => Some(Language::Go),
+            "java" => Some(Language::Java),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::TypeScript => "typescript",
+            Language::JavaScript => "javascript",
+            Language::Python => "python",
+            Language::Go => "go",
+            Language::Java => "java",
+        }
+    }
+
+    /// 定義の名前の前に付くキーワード
+    fn keywords(&self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => {
+                &["fn", "struct", "enum", "trait", "mod", "const", "static", "type", "union", "macro_rules"]
+            }
+            Language::TypeScript | Language::JavaScript => {
+                &["function", "class", "interface", "type", "enum", "namespace", "module", "const", "let", "var"]
+            }
+            Language::Python => &["def", "class"],
+            Language::Go => &["func", "type", "var", "const"],
+            Language::Java => &["class", "interface", "enum", "record"],
+        }
+    }
+
+    /// 中の定義ごとに区切るキーワード
+    fn containers(&self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &["impl", "trait", "mod"],
+            Language::TypeScript | Language::JavaScript => &["class", "interface", "namespace"],
+            Language::Python => &["class"],
+            Language::Go => &[],
+            Language::Java => &["class", "interface", "enum", "record"],
+        }
+    }
+
+    fn separator(&self) -> &'static str {
+        match self {
+            Language::Rust => "::",
+            _ => ".",
+        }
+    }
+
+    fn line_comment(&self) -> &'static str {
+        match self {
+            Language::Python => "#",
+            _ => "//",
+        }
+    }
+
+    /// 中括弧で本体を囲む言語 ( Python 以外 )
+    fn has_braces(&self) -> bool {
+        *self != Language::Python
+    }
+}
+
+impl Extractor for CodeExtractor {
+    fn name(&self) -> &str {
+        "code"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &EXTENSIONS
+    }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDocument> {
+        let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_lowercase();
+        let (text, _) = encoding::read_text(path, None)?;
+        let lines: Vec<&str> = text.lines().collect();
+
+        let (name, items) = match Language::from_extension(&ext) {
+            Some(language) => (language.as_str(), merge(items(language, &lines))),
+            None => {
+                let name = FALLBACK_LANGUAGES.iter().find(|(e, _)| *e == ext).map_or(ext.as_str(), |(_, name)| name);
+                (name, windows(&lines))
+            }
+        };
+
+        let mut sections = vec![];
+        for item in items {
+            // 前後の空行は含めない
+            let Some(start) = item.range.clone().find(|i| !lines[*i].trim().is_empty()) else {
+                continue;
+            };
+            let end = item.range.clone().rev().find(|i| !lines[*i].trim().is_empty()).unwrap() + 1;
+
+            let mut attributes = Attributes::from([
+                ("language".to_string(), json!(name)),
+                ("line_start".to_string(), json!(start + 1)),
+                ("line_end".to_string(), json!(end)),
+            ]);
+            if !item.symbols.is_empty() {
+                attributes.insert("symbol".to_string(), json!(item.symbols.join(", ")));
+            }
+            let block = Block::Code { code: lines[start..end].join("\n"), fence: Some(format!("```{}", name)) };
+            sections.push(Section { blocks: vec![block], attributes });
+        }
+        Ok(ExtractedDocument { sections })
+    }
+}
+
+/// 連続する行の範囲と、その中の定義の名前
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    /// 0 始まりの行の範囲
+    pub range: Range<usize>,
+    pub symbols: Vec<String>,
+}
+
+/// 全ての行を定義ごとに分ける ( 定義の前のコメントや属性はその定義に含める )
+pub fn items(language: Language, lines: &[&str]) -> Vec<Item> {
+    let scanned = scan(language, lines);
+    let parser = Parser { language, lines, scanned: &scanned };
+    parser.segment(0..lines.len(), 0, Some(0), None)
+}
+
+/// 小さな定義を続けてまとめる
+fn merge(items: Vec<Item>) -> Vec<Item> {
+    let mut merged: Vec<Item> = vec![];
+    for item in items {
+        if let Some(last) = merged.last_mut() {
+            if item.range.end - last.range.start <= MERGE_LINES {
+                last.range.end = item.range.end;
+                for symbol in item.symbols {
+                    if !last.symbols.contains(&symbol) {
+                        last.symbols.push(symbol);
+                    }
+                }
+                continue;
+            }
+        }
+        merged.push(item);
+    }
+    merged
+}
+
+/// 行数で区切る ( なるべく空行で区切る )
+fn windows(lines: &[&str]) -> Vec<Item> {
+    let mut items = vec![];
+    let mut start = 0;
+    while start < lines.len() {
+        let mut end = (start + FALLBACK_LINES).min(lines.len());
+        if end < lines.len() {
+            let blank = (start + FALLBACK_LINES * 3 / 4..end).rev().find(|i| lines[*i].trim().is_empty());
+            end = blank.map_or(end, |i| i + 1);
+        }
+        items.push(Item { range: start..end, symbols: vec![] });
+        start = end;
+    }
+    items
+}
+
+/// 行頭の状態
+#[derive(Debug, Default, Clone, Copy)]
+struct Line {
+    /// 括弧の深さ
+    depth: usize,
+    indent: usize,
+    blank: bool,
+    /// 文字列やコメントの途中から始まる行、または前の行から続く行
+    continued: bool,
+    /// コメントのみの行
+    comment: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Mode {
+    Code,
+    BlockComment,
+    /// 閉じる文字列と、`\` によるエスケープの有無
+    Str(String, bool),
+}
+
+/// 文字列とコメントを除いて括弧の深さを数える
+fn scan(language: Language, lines: &[&str]) -> Vec<Line> {
+    let mut scanned = vec![];
+    let mut depth: isize = 0;
+    let mut mode = Mode::Code;
+    let mut backslash = false;
+
+    for line in lines {
+        let trimmed = line.trim_start();
+        scanned.push(Line {
+            depth: depth.max(0) as usize,
+            indent: line.len() - trimmed.len(),
+            blank: trimmed.is_empty(),
+            continued: mode != Mode::Code || backslash,
+            comment: mode == Mode::BlockComment
+                || trimmed.starts_with(language.line_comment())
+                || (language.has_braces() && trimmed.starts_with("/*")),
+        });
+
+        let chars: Vec<char> = line.chars().collect();
+        let starts_with = |i: usize, s: &str| s.chars().enumerate().all(|(j, c)| chars.get(i + j) == Some(&c));
+        let mut i = 0;
+        while i < chars.len() {
+            match &mode {
+                Mode::BlockComment => {
+                    if starts_with(i, "*/") {
+                        mode = Mode::Code;
+                        i += 1;
+                    }
+                }
+                Mode::Str(close, escape) => {
+                    if *escape && chars[i] == '\\' {
+                        i += 1;
+                    } else if starts_with(i, close) {
+                        i += close.chars().count() - 1;
+                        mode = Mode::Code;
+                    }
+                }
+                Mode::Code => {
+                    let c = chars[i];
+                    if starts_with(i, language.line_comment()) {
+                        break;
+                    } else if language.has_braces() && starts_with(i, "/*") {
+                        mode = Mode::BlockComment;
+                        i += 1;
+                    } else if language == Language::Python && (starts_with(i, "\"\"\"") || starts_with(i, "'''")) {
+                        mode = Mode::Str(c.to_string().repeat(3), true);
+                        i += 2;
+                    } else if let Some((close, length)) = raw_string(language, &chars, i) {
+                        mode = Mode::Str(close, false);
+                        i += length - 1;
+                    } else if c == '\'' && language == Language::Rust {
+                        // ライフタイム ( `'a` ) は文字リテラルではない
+                        if chars.get(i + 1) == Some(&'\\') {
+                            mode = Mode::Str("'".to_string(), true);
+                        } else if chars.get(i + 2) == Some(&'\'') {
+                            i += 2;
+                        }
+                    } else if c == '"' || c == '\'' {
+                        mode = Mode::Str(c.to_string(), true);
+                    } else if c == '`' && matches!(language, Language::TypeScript | Language::JavaScript) {
+                        mode = Mode::Str("`".to_string(), true);
+                    } else if c == '`' && language == Language::Go {
+                        mode = Mode::Str("`".to_string(), false);
+                    } else if matches!(c, '{' | '(' | '[') {
+                        depth += 1;
+                    } else if matches!(c, '}' | ')' | ']') {
+                        depth -= 1;
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        // 複数行にまたがらない文字列は行末で閉じる
+        if let Mode::Str(close, _) = &mode {
+            let multiline = close.len() == 3
+                || close == "`"
+                || (language == Language::Rust && (close.starts_with('"') || close == "'"));
+            if !multiline {
+                mode = Mode::Code;
+            }
+        }
+        backslash = mode == Mode::Code && line.trim_end().ends_with('\\');
+    }
+    scanned
+}
+
+/// Rust の生文字列 ( `r#"..."#` ) の閉じる文字列と開始部分の長さ
+fn raw_string(language: Language, chars: &[char], i: usize) -> Option<(String, usize)> {
+    if language != Language::Rust || chars[i] != 'r' || i > 0 && is_identifier(chars[i - 1]) {
+        return None;
+    }
+    let hashes = chars[i + 1..].iter().take_while(|c| **c == '#').count();
+    (chars.get(i + 1 + hashes) == Some(&'"')).then(|| (format!("\"{}", "#".repeat(hashes)), hashes + 2))
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// 前の行から続く構文の先頭の単語 ( 新しい定義の始まりではない )
+const CONTINUATIONS: [&str; 6] = ["where", "else", "elif", "except", "finally", "catch"];
+
+struct Parser<'a> {
+    language: Language,
+    lines: &'a [&'a str],
+    scanned: &'a [Line],
+}
+
+impl Parser<'_> {
+    /// range の中の、括弧の深さが depth でインデントが indent ( None の場合は最初の定義に合わせる ) の定義ごとに分ける
+    fn segment(&self, range: Range<usize>, depth: usize, indent: Option<usize>, parent: Option<&str>) -> Vec<Item> {
+        let is_candidate = |i: usize| {
+            let line = &self.scanned[i];
+            let first = self.lines[i].trim_start().chars().next().unwrap_or(' ');
+            let word: String = self.lines[i].trim_start().chars().take_while(|c| is_identifier(*c)).collect();
+            !line.blank
+                && !line.continued
+                && !line.comment
+                && line.depth == depth
+                && (first.is_alphabetic() || first == '_' || first == '$')
+                && !CONTINUATIONS.contains(&word.as_str())
+        };
+        let Some(indent) = indent.or_else(|| range.clone().find(|i| is_candidate(*i)).map(|i| self.scanned[i].indent))
+        else {
+            return vec![Item { range, symbols: vec![] }];
+        };
+
+        // 定義の先頭行と、直前のコメントや属性を含めた開始行
+        let mut starts: Vec<(usize, usize)> = vec![];
+        for i in range.clone().filter(|i| is_candidate(*i) && self.scanned[*i].indent == indent) {
+            let lower = starts.last().map_or(range.start, |(_, line)| line + 1);
+            let mut start = i;
+            while start > lower && self.is_prefix(start - 1, depth) {
+                start -= 1;
+            }
+            starts.push((start, i));
+        }
+
+        let mut items = vec![];
+        if starts.first().is_none_or(|(start, _)| *start > range.start) {
+            let end = starts.first().map_or(range.end, |(start, _)| *start);
+            items.push(Item { range: range.start..end, symbols: vec![] });
+        }
+        for (k, (start, line)) in starts.iter().enumerate() {
+            let end = starts.get(k + 1).map_or(range.end, |(next, _)| *next);
+            items.extend(self.definition(*start..end, *line, depth, parent));
+        }
+
+        // 名前のない定義 ( import など ) は続けてまとめる
+        let mut merged: Vec<Item> = vec![];
+        for item in items {
+            match merged.last_mut() {
+                Some(last) if last.symbols.is_empty() && item.symbols.is_empty() => last.range.end = item.range.end,
+                _ => merged.push(item),
+            }
+        }
+        merged
+    }
+
+    /// 1 つの定義 ( クラスなどは中の定義ごとに分ける )
+    fn definition(&self, range: Range<usize>, line: usize, depth: usize, parent: Option<&str>) -> Vec<Item> {
+        let name = symbol(self.language, self.lines[line], parent.is_some());
+        let full_name = match (parent, &name) {
+            (Some(parent), Some(name)) => Some(format!("{}{}{}", parent, self.language.separator(), name)),
+            _ => name.clone(),
+        };
+        let symbols: Vec<String> = full_name.clone().into_iter().collect();
+
+        let first_word = self.lines[line]
+            .split(|c: char| !is_identifier(c))
+            .find(|word| self.language.containers().contains(word) || self.language.keywords().contains(word));
+        let is_container = first_word.is_some_and(|word| self.language.containers().contains(&word));
+        if !is_container || range.end - line <= 1 {
+            return vec![Item { range, symbols }];
+        }
+
+        // 本体の中の定義 ( Python はインデントのみで判定する )
+        let body = line + 1..range.end;
+        let (body_depth, body_indent) = if self.language.has_braces() {
+            (depth + 1, None)
+        } else {
+            let indent = body.clone().find(|i| !self.scanned[*i].blank).map(|i| self.scanned[i].indent);
+            match indent {
+                Some(indent) if indent > self.scanned[line].indent => (depth, Some(indent)),
+                _ => return vec![Item { range, symbols }],
+            }
+        };
+        let parent = full_name.unwrap_or_default();
+        let members = self.segment(body, body_depth, body_indent, Some(&parent));
+        if members.iter().all(|member| member.symbols.is_empty()) {
+            return vec![Item { range, symbols }];
+        }
+
+        let mut items = vec![Item { range: range.start..line + 1, symbols }];
+        items.extend(members);
+        items
+    }
+
+    /// 定義の直前のコメントや属性 ( `#[...]`, `@...` ) の行
+    fn is_prefix(&self, i: usize, depth: usize) -> bool {
+        let line = &self.scanned[i];
+        let trimmed = self.lines[i].trim_start();
+        !line.blank
+            && line.depth == depth
+            && (line.comment
+                || trimmed.starts_with('@')
+                || (self.language == Language::Rust && trimmed.starts_with("#[")))
+    }
+}
+
+/// 定義の先頭行から定義の名前を取り出す ( クラスなどの中ではメソッド名やフィールド名も取り出す )
+fn symbol(language: Language, line: &str, member: bool) -> Option<String> {
+    let code = line.split(language.line_comment()).next().unwrap_or_default();
+    let words: Vec<&str> = code.split(|c: char| !is_identifier(c)).filter(|word| !word.is_empty()).collect();
+
+    // 引数や戻り値の `impl Trait` ではなく、最初のキーワードが `impl` の場合のみ
+    let first_keyword =
+        words.iter().find(|word| language.containers().contains(word) || language.keywords().contains(word));
+    if language == Language::Rust && first_keyword == Some(&"impl") {
+        // `impl<T> Trait for Type<T>` は Type
+        let target = code.split(" for ").nth(1).unwrap_or_else(|| code.split_once("impl").unwrap().1);
+        let target = if target.starts_with('<') { skip_generics(target) } else { target };
+        return target.split(|c: char| !is_identifier(c) && c != ':').find(|word| !word.is_empty()).map(String::from);
+    }
+    if language == Language::Go
+        && words.first() == Some(&"func")
+        && code.trim_start()[4..].trim_start().starts_with('(')
+    {
+        // メソッドは `Receiver.Method`
+        let (receiver, rest) = code.split_once(')')?;
+        let receiver = receiver.split(|c: char| !is_identifier(c)).rfind(|word| !word.is_empty())?;
+        let method = rest.split(|c: char| !is_identifier(c)).find(|word| !word.is_empty())?;
+        return Some(format!("{}.{}", receiver, method));
+    }
+
+    for (i, word) in words.iter().enumerate() {
+        if language.keywords().contains(word) {
+            let next = words.get(i + 1)?;
+            // `const fn` などはその後のキーワードを使う
+            if language.keywords().contains(next) && words.get(i + 2).is_some() {
+                continue;
+            }
+            return Some(next.to_string());
+        }
+    }
+
+    // キーワードのないメソッドやフィールドは `(` `=` `:` `;` の直前の名前
+    if member && matches!(language, Language::TypeScript | Language::JavaScript | Language::Python | Language::Java) {
+        let end = code.find(['(', '=', ':', ';', '{']).unwrap_or(code.len());
+        return code[..end].split(|c: char| !is_identifier(c)).rfind(|word| !word.is_empty()).map(String::from);
+    }
+    None
+}
+
+fn skip_generics(text: &str) -> &str {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return &text[i + 1..];
+                }
+            }
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols(language: Language, code: &str) -> Vec<(Range<usize>, Vec<String>)> {
+        let lines: Vec<&str> = code.lines().collect();
+        items(language, &lines).into_iter().map(|item| (item.range, item.symbols)).collect()
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn rust() {
+        let code = r##"use std::fmt;
+use std::path::Path;
+
+/// 文字列 "{" を含む
+#[derive(Debug)]
+pub struct Point<'a> {
+    name: &'a str,
+}
+
+impl<'a> fmt::Display for Point<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, r#"{"#)
+    }
+
+    pub const fn new(name: &'a str) -> Self
+    where
+        Self: Sized,
+    {
+        Self { name }
+    }
+
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.name.chars()
+    }
+}
+
+pub fn main() {
+    let c = '{';
+}
+
+fn parse(input: impl AsRef<str>) -> impl Fn() {
+}
+"##;
+        assert_eq!(
+            symbols(Language::Rust, code),
+            vec![
+                (0..3, vec![]),
+                (3..9, names(&["Point"])),
+                (9..10, names(&["Point"])),
+                (10..14, names(&["Point::fmt"])),
+                (14..21, names(&["Point::new"])),
+                (21..26, names(&["Point::chars"])),
+                (26..30, names(&["main"])),
+                (30..32, names(&["parse"])),
+            ]
+        );
+    }
+
+    #[test]
+    fn languages() {
+        let python = "import os\n\n@dataclass\nclass User:\n    name: str\n\n    def greet(self):\n        return \"\"\"\ndef x():\n\"\"\"\n\nasync def main():\n    pass\n";
+        assert_eq!(
+            symbols(Language::Python, python),
+            vec![
+                (0..2, vec![]),
+                (2..4, names(&["User"])),
+                (4..6, names(&["User.name"])),
+                (6..11, names(&["User.greet"])),
+                (11..13, names(&["main"])),
+            ]
+        );
+
+        let go = "package main\n\nfunc (s *Server) Start(ctx context.Context) error {\n\treturn `}`\n}\n\ntype Server struct {\n}\n";
+        assert_eq!(
+            symbols(Language::Go, go),
+            vec![(0..2, vec![]), (2..6, names(&["Server.Start"])), (6..8, names(&["Server"]))]
+        );
+
+        let typescript = "export default class Api {\n  private url: string;\n  async fetch(path: string) {\n    return `${this.url}{`;\n  }\n}\nexport const handler = () => {};\n";
+        assert_eq!(
+            symbols(Language::TypeScript, typescript),
+            vec![
+                (0..1, names(&["Api"])),
+                (1..2, names(&["Api.url"])),
+                (2..6, names(&["Api.fetch"])),
+                (6..7, names(&["handler"])),
+            ]
+        );
+
+        let java = "package a;\n\npublic class Main {\n    @Override\n    public String toString() {\n        return \"}\";\n    }\n}\n";
+        assert_eq!(
+            symbols(Language::Java, java),
+            vec![(0..2, vec![]), (2..3, names(&["Main"])), (3..8, names(&["Main.toString"]))]
+        );
+    }
+
+    #[test]
+    fn extract() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        let long: String = (0..40).map(|i| format!("    let x{} = {};\n", i, i)).collect();
+        std::fs::write(&path, format!("fn a() {{}}\n\nfn b() {{}}\n\nfn c() {{\n{}}}\n", long)).unwrap();
+
+        let sections = CodeExtractor.extract(&path).unwrap().sections;
+        let attributes: Vec<_> = sections
+            .iter()
+            .map(|s| {
+                (s.attributes["symbol"].clone(), s.attributes["line_start"].clone(), s.attributes["line_end"].clone())
+            })
+            .collect();
+        assert_eq!(attributes, vec![(json!("a, b"), json!(1), json!(3)), (json!("c"), json!(5), json!(46))]);
+        assert_eq!(sections[0].attributes["language"], json!("rust"));
+        assert_eq!(
+            sections[0].blocks,
+            vec![Block::Code { code: "fn a() {}\n\nfn b() {}".to_string(), fence: Some("```rust".to_string()) }]
+        );
+
+        // 対応していない言語は行数で区切る
+        let path = dir.path().join("query.sql");
+        let lines: String =
+            (0..50).map(|i| if i == 35 { "\n".to_string() } else { format!("SELECT {};\n", i) }).collect();
+        std::fs::write(&path, lines).unwrap();
+        let sections = CodeExtractor.extract(&path).unwrap().sections;
+        let ranges: Vec<_> =
+            sections.iter().map(|s| (s.attributes["line_start"].clone(), s.attributes["line_end"].clone())).collect();
+        assert_eq!(ranges, vec![(json!(1), json!(35)), (json!(37), json!(50))]);
+        assert!(!sections[0].attributes.contains_key("symbol"));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::chroma::document::CollectionName;
+use crate::config::CollectionConfig;
+use anyhow::{Context, Result};
+use globset::{Glob, GlobMatcher};
+use sha2::{Digest, Sha256};
+
+/// コレクション名の最短・最長の文字数 ( Chroma DB の制約 )
+const MIN_LENGTH: usize = 3;
+const MAX_LENGTH: usize = 63;
+
+/// 名前を置き換えた場合に付ける元の名前のハッシュの文字数
+const HASH_LENGTH: usize = 8;
+
+/// ファイルのパスからコレクション名を決める
+///
+/// 規則のパターンに一致すればその名前、一致しなければ先頭から depth 階層分のディレクトリ名を `-` で繋げた名前にする
+#[derive(Debug, Clone)]
+pub struct CollectionMapper {
+    depth: usize,
+    root: String,
+    rules: Vec<(GlobMatcher, String)>,
+}
+
+impl Default for CollectionMapper {
+    fn default() -> Self {
+        Self::new(&CollectionConfig::default()).unwrap()
+    }
+}
+
+impl CollectionMapper {
+    pub fn new(config: &CollectionConfig) -> Result<Self> {
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                let glob = Glob::new(&rule.pattern).with_context(|| format!("Invalid pattern: {}", rule.pattern))?;
+                Ok((glob.compile_matcher(), rule.name.clone()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { depth: config.depth, root: config.root.clone(), rules })
+    }
+
+    /// 対象ディレクトリからの相対パスに対応するコレクション名
+    pub fn name(&self, path: &str) -> CollectionName {
+        if let Some((_, name)) = self.rules.iter().find(|(matcher, _)| matcher.is_match(path)) {
+            return sanitize(name);
+        }
+
+        let dirs: Vec<&str> = path.split('/').collect();
+        let dirs = &dirs[..dirs.len() - 1];
+        if dirs.is_empty() || self.depth == 0 {
+            sanitize(&self.root)
+        } else {
+            sanitize(&dirs[..dirs.len().min(self.depth)].join("-"))
+        }
+    }
+}
+
+/// Chroma DB で使える名前に置き換える
+///
+/// 英数字と `-` `_` 以外 ( 日本語、空白、`.` など ) は `-` に置き換え、置き換えた場合は名前が重複しないように元の名前のハッシュを付ける
+pub fn sanitize(name: &str) -> CollectionName {
+    let mut sanitized = String::new();
+    for c in name.chars() {
+        let c = if c.is_ascii_alphanumeric() || c == '_' { c } else { '-' };
+        if !(c == '-' && sanitized.ends_with('-')) {
+            sanitized.push(c);
+        }
+    }
+    let mut sanitized = sanitized.trim_matches(['-', '_']).to_string();
+
+    if sanitized == name && (MIN_LENGTH..=MAX_LENGTH).contains(&name.len()) {
+        return sanitized;
+    }
+
+    // 先頭と末尾は英数字にする必要があるため、ハッシュは末尾に付ける
+    let hash: String = Sha256::digest(name.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect();
+    sanitized.truncate(MAX_LENGTH - HASH_LENGTH - 1);
+    let sanitized = sanitized.trim_end_matches(['-', '_']);
+    if sanitized.is_empty() {
+        hash[..HASH_LENGTH].to_string()
+    } else {
+        format!("{}-{}", sanitized, &hash[..HASH_LENGTH])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CollectionRule;
+
+    #[test]
+    fn mapping() {
+        let mapper = CollectionMapper::default();
+        assert_eq!(mapper.name("a.txt"), "root");
+        assert_eq!(mapper.name("pj1/a.txt"), "pj1");
+        assert_eq!(mapper.name("pj1/dir1/dir2/a.txt"), "pj1-dir1");
+
+        let config = CollectionConfig {
+            depth: 0,
+            root: "all".to_string(),
+            rules: vec![
+                CollectionRule { pattern: "docs/**/*.md".to_string(), name: "manual".to_string() },
+                CollectionRule { pattern: "docs/**".to_string(), name: "docs".to_string() },
+            ],
+        };
+        let mapper = CollectionMapper::new(&config).unwrap();
+        assert_eq!(mapper.name("pj1/dir1/a.txt"), "all");
+        assert_eq!(mapper.name("docs/api/a.md"), "manual");
+        assert_eq!(mapper.name("docs/api/a.txt"), "docs");
+
+        let config = CollectionConfig { depth: 1, ..Default::default() };
+        assert_eq!(CollectionMapper::new(&config).unwrap().name("pj1/dir1/a.txt"), "pj1");
+
+        let config = CollectionConfig {
+            rules: vec![CollectionRule { pattern: "[".to_string(), name: "x".to_string() }],
+            ..Default::default()
+        };
+        assert!(CollectionMapper::new(&config).is_err());
+    }
+
+    #[test]
+    fn sanitized() {
+        assert_eq!(sanitize("health-care_api"), "health-care_api");
+
+        let japanese = sanitize("議事録-2024");
+        assert!(japanese.starts_with("2024-"), "{}", japanese);
+        assert_ne!(japanese, sanitize("仕様書-2024"));
+
+        assert!(sanitize("my docs").starts_with("my-docs-"));
+        assert!(sanitize("v1.2").starts_with("v1-2-"));
+        assert_eq!(sanitize("議事録").len(), HASH_LENGTH);
+        assert_eq!(sanitize("ab").len(), "ab-".len() + HASH_LENGTH);
+        assert_eq!(sanitize(&"a".repeat(100)).len(), MAX_LENGTH);
+
+        for name in ["議事録-2024", "my docs", "v1.2", "議事録", "ab", "-a-", "_x_y_"] {
+            let sanitized = sanitize(name);
+            assert!((MIN_LENGTH..=MAX_LENGTH).contains(&sanitized.len()), "{}", sanitized);
+            assert!(sanitized.starts_with(|c: char| c.is_ascii_alphanumeric()), "{}", sanitized);
+            assert!(sanitized.ends_with(|c: char| c.is_ascii_alphanumeric()), "{}", sanitized);
+        }
+    }
+}
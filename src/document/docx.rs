@@ -0,0 +1,179 @@
+use crate::document::extractor::{Block, ExtractedDocument, Extractor, Outline};
+use crate::document::office::{self, attribute, child, children, Package};
+use anyhow::{anyhow, Context, Result};
+use roxmltree::Node;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// 見出しとして扱わない段落のアウトラインレベル ( 本文 )
+const BODY_OUTLINE_LEVEL: usize = 9;
+
+/// Word ( .docx ) の段落・見出し・表
+///
+/// 見出しごとに区切り、Markdown と同じ形式で見出しの階層を付与する。
+/// 表は 1 行を `セル | セル` の 1 行にまとめ、行の途中では分割しない
+pub struct DocxExtractor;
+
+impl Extractor for DocxExtractor {
+    fn name(&self) -> &str {
+        "docx"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["docx"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDocument> {
+        let mut package = Package::open(path)?;
+        let styles = match package.read("word/styles.xml")? {
+            Some(xml) => heading_styles(&xml)?,
+            None => HashMap::new(),
+        };
+        let xml = package.read_required("word/document.xml")?;
+        let document = roxmltree::Document::parse(&xml).context("Invalid XML: word/document.xml")?;
+        let body = child(document.root_element(), "body").ok_or_else(|| anyhow!("Missing body: {}", path.display()))?;
+
+        let mut outline = Outline::default();
+        visit(&mut outline, body, &styles);
+        Ok(ExtractedDocument { sections: outline.finish() })
+    }
+}
+
+fn visit(outline: &mut Outline, node: Node, styles: &HashMap<String, usize>) {
+    for node in node.children().filter(Node::is_element) {
+        match node.tag_name().name() {
+            "p" => {
+                let text = paragraph_text(node);
+                if text.trim().is_empty() {
+                    continue;
+                }
+                match heading_level(node, styles) {
+                    Some(level) => outline.heading(level, text),
+                    None => outline.push(Block::Text(text)),
+                }
+            }
+            "tbl" => {
+                let rows = table_rows(node);
+                if !rows.is_empty() {
+                    outline.push(Block::Code { code: rows.join("\n"), fence: None });
+                }
+            }
+            // コンテンツコントロール ( 目次など )
+            "sdt" => {
+                if let Some(content) = child(node, "sdtContent") {
+                    visit(outline, content, styles);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 段落のテキスト ( 段落の設定に含まれるタブ位置などは除く )
+fn paragraph_text(paragraph: Node) -> String {
+    let mut text = String::new();
+    for node in paragraph.descendants().filter(|node| node.parent().is_some_and(|p| p.tag_name().name() == "r")) {
+        match node.tag_name().name() {
+            "t" => text.push_str(node.text().unwrap_or_default()),
+            "tab" => text.push('\t'),
+            "br" | "cr" => text.push('\n'),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// 表の各行 ( 入れ子の表はセル内のテキストとしてまとめる )
+fn table_rows(table: Node) -> Vec<String> {
+    children(table, "tr")
+        .map(|row| children(row, "tc").map(cell_text).collect::<Vec<_>>())
+        .filter(|cells| cells.iter().any(|cell| !cell.is_empty()))
+        .map(|cells| cells.join(" | "))
+        .collect()
+}
+
+fn cell_text(cell: Node) -> String {
+    let texts: Vec<String> = cell
+        .children()
+        .filter_map(|node| match node.tag_name().name() {
+            "p" => Some(paragraph_text(node)),
+            "tbl" => Some(table_rows(node).join(" ")),
+            _ => None,
+        })
+        .collect();
+    office::single_line(&texts.join(" "))
+}
+
+/// 段落の見出しレベル ( 段落のアウトラインレベル、段落スタイルの順に判定する )
+fn heading_level(paragraph: Node, styles: &HashMap<String, usize>) -> Option<usize> {
+    let properties = child(paragraph, "pPr")?;
+    if let Some(level) = child(properties, "outlineLvl").and_then(outline_level) {
+        return (level < BODY_OUTLINE_LEVEL).then_some(level + 1);
+    }
+    let style = child(properties, "pStyle").and_then(|style| attribute(style, "val"))?;
+    styles.get(style).copied()
+}
+
+fn outline_level(node: Node) -> Option<usize> {
+    attribute(node, "val")?.parse().ok()
+}
+
+/// 見出しの段落スタイルの ID とレベル ( 日本語版の Word でもスタイル名は `heading 1` になる )
+fn heading_styles(xml: &str) -> Result<HashMap<String, usize>> {
+    let document = roxmltree::Document::parse(xml).context("Invalid XML: word/styles.xml")?;
+    let mut styles = HashMap::new();
+    for style in children(document.root_element(), "style") {
+        let Some(id) = attribute(style, "styleId") else {
+            continue;
+        };
+        let outline =
+            child(style, "pPr").and_then(|properties| child(properties, "outlineLvl")).and_then(outline_level);
+        let level = match outline {
+            Some(level) => (level < BODY_OUTLINE_LEVEL).then_some(level + 1),
+            None => child(style, "name")
+                .and_then(|name| attribute(name, "val"))
+                .and_then(|name| name.to_lowercase().strip_prefix("heading ")?.trim().parse().ok()),
+        };
+        if let Some(level) = level {
+            styles.insert(id.to_string(), level);
+        }
+    }
+    Ok(styles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn docx() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spec.docx");
+        let styles = r#"<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+            <w:style w:type="paragraph" w:styleId="1"><w:name w:val="heading 1"/></w:style>
+            <w:style w:type="paragraph" w:styleId="a"><w:name w:val="Normal"/></w:style>
+        </w:styles>"#;
+        let document = r#"<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>
+            <w:p><w:r><w:t>前書き</w:t></w:r></w:p>
+            <w:p><w:pPr><w:pStyle w:val="1"/><w:tabs><w:tab w:val="left" w:pos="840"/></w:tabs></w:pPr><w:r><w:t>概要</w:t></w:r></w:p>
+            <w:p><w:pPr><w:pStyle w:val="a"/></w:pPr><w:r><w:t>名前</w:t><w:tab/><w:t>説明</w:t></w:r></w:p>
+            <w:p><w:pPr><w:outlineLvl w:val="1"/></w:pPr><w:r><w:t xml:space="preserve">認証 </w:t></w:r><w:r><w:t>API</w:t></w:r></w:p>
+            <w:tbl>
+                <w:tr><w:tc><w:p><w:r><w:t>項目</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>値</w:t></w:r></w:p></w:tc></w:tr>
+                <w:tr><w:tc><w:p><w:r><w:t>token</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>必須</w:t></w:r></w:p><w:p><w:r><w:t>64 文字</w:t></w:r></w:p></w:tc></w:tr>
+            </w:tbl>
+            <w:sectPr/>
+        </w:body></w:document>"#;
+        office::write_package(&path, &[("word/styles.xml", styles), ("word/document.xml", document)]);
+
+        let sections = DocxExtractor.extract(&path).unwrap().sections;
+        let headings: Vec<Option<&str>> =
+            sections.iter().map(|s| s.attributes.get("heading").and_then(|h| h.as_str())).collect();
+        assert_eq!(headings, vec![None, Some("# 概要"), Some("# 概要 > ## 認証 API")]);
+        assert_eq!(sections[1].blocks, vec![Block::Text("概要".to_string()), Block::Text("名前\t説明".to_string())]);
+        assert_eq!(
+            sections[2].blocks[1],
+            Block::Code { code: "項目 | 値\ntoken | 必須 64 文字".to_string(), fence: None }
+        );
+    }
+}
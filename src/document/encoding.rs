@@ -0,0 +1,133 @@
+use crate::warn;
+use anyhow::{anyhow, Result};
+use encoding_rs::{Encoding, EUC_JP, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8};
+use std::path::Path;
+
+/// BOM がなく UTF-8 でもない場合に試す文字コード
+const CANDIDATES: [&Encoding; 2] = [SHIFT_JIS, EUC_JP];
+
+/// テキストファイルを読み込み、内容と文字コードを返す
+///
+/// encoding を指定しない場合は BOM → UTF-16 ( BOM なし ) → UTF-8 → Shift_JIS / EUC-JP の順に判定する
+pub fn read_text(path: &Path, encoding: Option<&'static Encoding>) -> Result<(String, &'static Encoding)> {
+    decode(&std::fs::read(path)?, encoding).map_err(|e| anyhow!("{}: {}", e, path.display()))
+}
+
+/// `--encoding` に指定する文字コード名 ( e.g. `shift_jis`, `euc-jp`, `utf-16le` )
+pub fn parse_label(label: &str) -> Result<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| anyhow!("Unknown encoding: {}", label))
+}
+
+pub fn decode(bytes: &[u8], encoding: Option<&'static Encoding>) -> Result<(String, &'static Encoding)> {
+    // BOM がある場合は BOM の文字コードを優先し、実際に使った文字コードを返す
+    if let Some(encoding) = encoding {
+        let (text, used, had_errors) = encoding.decode(bytes);
+        if had_errors {
+            return Err(anyhow!("Invalid {} text", used.name()));
+        }
+        return Ok((text.into_owned(), used));
+    }
+
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return decode_strict(&bytes[bom_length..], encoding)
+            .ok_or_else(|| anyhow!("Invalid {} text", encoding.name()));
+    }
+    // ASCII のみの UTF-16 は UTF-8 としても正しいため、先に判定する
+    if let Some(decoded) = utf16_without_bom(bytes).and_then(|encoding| decode_strict(bytes, encoding)) {
+        return Ok(decoded);
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Ok((text.to_string(), UTF_8));
+    }
+
+    // 両方で解釈できるバイト列もあるため、日本語として自然な文字の割合が高い方を選ぶ
+    let decoded = CANDIDATES
+        .iter()
+        .filter_map(|encoding| decode_strict(bytes, encoding))
+        .max_by(|(a, _), (b, _)| japanese_ratio(a).total_cmp(&japanese_ratio(b)));
+    if let Some(decoded) = decoded {
+        return Ok(decoded);
+    }
+
+    // 一部が壊れたファイルは、置き換えた文字が最も少ない文字コードで読み込む
+    let (text, encoding) = [UTF_8, SHIFT_JIS, EUC_JP]
+        .into_iter()
+        .map(|encoding| (encoding.decode_without_bom_handling(bytes).0.into_owned(), encoding))
+        .min_by_key(|(text, _)| text.matches(char::REPLACEMENT_CHARACTER).count())
+        .unwrap();
+    warn!("Invalid characters are replaced as {}", encoding.name());
+    Ok((text, encoding))
+}
+
+fn decode_strict(bytes: &[u8], encoding: &'static Encoding) -> Option<(String, &'static Encoding)> {
+    let text = encoding.decode_without_bom_handling_and_without_replacement(bytes)?;
+    Some((text.into_owned(), encoding))
+}
+
+/// ASCII の文字が多い UTF-16 は上位または下位のバイトが 0 に偏る
+fn utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 2 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let zeros = |offset: usize| bytes.iter().skip(offset).step_by(2).filter(|b| **b == 0).count();
+    let half = bytes.len() / 2;
+    if zeros(0) * 3 > half && zeros(1) == 0 {
+        Some(UTF_16BE)
+    } else if zeros(1) * 3 > half && zeros(0) == 0 {
+        Some(UTF_16LE)
+    } else {
+        None
+    }
+}
+
+/// ひらがな・カタカナ・漢字・全角記号と ASCII の割合
+fn japanese_ratio(text: &str) -> f64 {
+    let total = text.chars().count();
+    if total == 0 {
+        return 0.0;
+    }
+    let natural = text
+        .chars()
+        .filter(|c| {
+            c.is_ascii() || matches!(c, '\u{3000}'..='\u{30FF}' | '\u{4E00}'..='\u{9FFF}' | '\u{FF01}'..='\u{FF9F}')
+        })
+        .count();
+    natural as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        let text = "これは日本語のテキストです。ABC 123\n";
+        let detect = |bytes: Vec<u8>| decode(&bytes, None).unwrap();
+
+        assert_eq!(detect(text.as_bytes().to_vec()), (text.to_string(), UTF_8));
+        assert_eq!(detect([b"\xEF\xBB\xBF".as_slice(), text.as_bytes()].concat()), (text.to_string(), UTF_8));
+        for encoding in [SHIFT_JIS, EUC_JP] {
+            assert_eq!(detect(encoding.encode(text).0.into_owned()), (text.to_string(), encoding));
+        }
+
+        let utf16le: Vec<u8> = "plain ascii text\n".encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+        assert_eq!(detect(utf16le).1, UTF_16LE);
+        let utf16be: Vec<u8> = [0xFEFF].into_iter().chain(text.encode_utf16()).flat_map(|u| u.to_be_bytes()).collect();
+        assert_eq!(detect(utf16be), (text.to_string(), UTF_16BE));
+
+        // 指定した文字コードを優先する
+        let sjis = SHIFT_JIS.encode(text).0.into_owned();
+        assert_eq!(decode(&sjis, Some(SHIFT_JIS)).unwrap().1, SHIFT_JIS);
+        assert!(decode(&sjis, Some(UTF_8)).is_err());
+        let bom = [b"\xEF\xBB\xBF".as_slice(), text.as_bytes()].concat();
+        assert_eq!(decode(&bom, Some(SHIFT_JIS)).unwrap(), (text.to_string(), UTF_8));
+
+        assert_eq!(parse_label("Shift_JIS").unwrap(), SHIFT_JIS);
+        assert_eq!(parse_label("euc-jp").unwrap(), EUC_JP);
+        assert!(parse_label("unknown").is_err());
+
+        // 壊れたバイト列も置き換えて読み込む
+        let (broken, _) = decode(&[sjis.as_slice(), b"\xFF\xFF"].concat(), None).unwrap();
+        assert!(broken.starts_with(text), "{}", broken);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::chroma::document::Attributes;
+use crate::document::office;
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+/// 拡張子で判定できないファイルの判定に使う先頭バイト数
+const SNIFF_BYTES: usize = 512;
+
+/// ファイルから抽出したテキスト
+#[derive(Debug, Default)]
+pub struct ExtractedDocument {
+    pub sections: Vec<Section>,
+}
+
+impl ExtractedDocument {
+    /// 区切りのないテキスト
+    pub fn text(text: String) -> Self {
+        Self { sections: vec![Section::text(text, Attributes::new())] }
+    }
+
+    /// 全ての区切りにメタデータを付与する
+    pub fn with_attribute(mut self, key: &str, value: Value) -> Self {
+        for section in &mut self.sections {
+            section.attributes.insert(key.to_string(), value.clone());
+        }
+        self
+    }
+}
+
+/// ページや見出しなどの区切り ( チャンクは区切りをまたがない )
+#[derive(Debug, Default, Clone)]
+pub struct Section {
+    /// 段落などのまとまり ( チャンクはなるべくまとまりの境界で区切る )
+    pub blocks: Vec<Block>,
+    /// 区切りから作られる全てのチャンクに付与するメタデータ
+    pub attributes: Attributes,
+}
+
+impl Section {
+    pub fn text(text: String, attributes: Attributes) -> Self {
+        Self { blocks: vec![Block::Text(text)], attributes }
+    }
+}
+
+/// 見出しごとに区切りを組み立てる
+///
+/// 各区切りに Markdown と同じ形式で見出しの階層 ( e.g. `# 概要 > ## 認証` ) を付与する
+#[derive(Debug, Default)]
+pub struct Outline {
+    sections: Vec<Section>,
+    /// 見出しのレベルとテキスト
+    headings: Vec<(usize, String)>,
+    blocks: Vec<Block>,
+}
+
+impl Outline {
+    pub fn push(&mut self, block: Block) {
+        self.blocks.push(block);
+    }
+
+    /// 見出し自体も本文として残す
+    pub fn heading(&mut self, level: usize, text: String) {
+        let title = office::single_line(&text);
+        self.heading_with_title(level, title, text);
+    }
+
+    /// 本文に残す見出しの行 ( raw ) と階層に使うテキスト ( title ) が異なる場合 ( e.g. Markdown の装飾 )
+    pub fn heading_with_title(&mut self, level: usize, title: String, raw: String) {
+        self.flush_section();
+
+        self.headings.retain(|(l, _)| *l < level);
+        self.headings.push((level, title));
+        self.blocks.push(Block::Text(raw));
+    }
+
+    pub fn finish(mut self) -> Vec<Section> {
+        self.flush_section();
+        self.sections
+    }
+
+    fn flush_section(&mut self) {
+        if self.blocks.is_empty() {
+            return;
+        }
+
+        let mut attributes = Attributes::new();
+        if !self.headings.is_empty() {
+            let breadcrumb: Vec<String> =
+                self.headings.iter().map(|(level, text)| format!("{} {}", "#".repeat(*level), text)).collect();
+            attributes.insert("heading".to_string(), json!(breadcrumb.join(" > ")));
+        }
+        self.sections.push(Section { blocks: std::mem::take(&mut self.blocks), attributes });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    /// 任意の位置で分割できるテキスト
+    Text(String),
+    /// 行の途中では分割しないコード ( フェンスがある場合は分割後の各チャンクをフェンスで囲む )
+    Code { code: String, fence: Option<String> },
+}
+
+impl Block {
+    /// チャンクに含める文字列
+    pub fn render(&self) -> String {
+        match self {
+            Block::Text(text) => text.clone(),
+            Block::Code { code, fence: None } => code.clone(),
+            Block::Code { code, fence: Some(fence) } => format!("{}\n{}\n{}", fence, code, closing_fence(fence)),
+        }
+    }
+}
+
+/// 開始フェンス ( e.g. ```rust ) に対応する終了フェンス
+pub fn closing_fence(fence: &str) -> String {
+    let fence = fence.trim_start();
+    let marker = fence.chars().next().unwrap_or('`');
+    fence.chars().take_while(|c| *c == marker).collect()
+}
+
+/// ファイル形式ごとのテキスト抽出
+pub trait Extractor: Send + Sync {
+    /// ログなどに表示する名前
+    fn name(&self) -> &str;
+
+    /// 対応する拡張子 ( 小文字、ドットなし )
+    fn extensions(&self) -> &[&str];
+
+    /// 拡張子のないファイルについて、先頭のバイト列から対応可否を判定する
+    fn sniff(&self, _head: &[u8]) -> bool {
+        false
+    }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDocument>;
+}
+
+/// ファイルに対応する Extractor の一覧
+///
+/// 後から登録したものが優先されるため、デフォルトの Extractor を上書きできる
+#[derive(Clone, Default)]
+pub struct ExtractorRegistry {
+    extractors: Vec<Arc<dyn Extractor>>,
+}
+
+impl ExtractorRegistry {
+    /// 何も登録されていない一覧 ( デフォルトの Extractor を含む一覧は `ExtractorRegistry::with_defaults` )
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::with_attachment_defaults();
+        let attachments = registry.clone();
+        registry.register(super::mail::MailExtractor::new(attachments));
+        registry
+    }
+
+    /// メールの添付ファイルに使うデフォルトの Extractor ( メール以外 )
+    pub fn with_attachment_defaults() -> Self {
+        let mut registry = Self::new();
+        registry
+            .register(super::text::TextExtractor::default())
+            .register(super::markdown::MarkdownExtractor::default())
+            .register(super::pdf::PdfExtractor)
+            .register(super::docx::DocxExtractor)
+            .register(super::xlsx::XlsxExtractor)
+            .register(super::pptx::PptxExtractor)
+            .register(super::html::HtmlExtractor)
+            .register(super::code::CodeExtractor)
+            .register(super::table::TableExtractor::default());
+        registry
+    }
+
+    pub fn register(&mut self, extractor: impl Extractor + 'static) -> &mut Self {
+        self.extractors.push(Arc::new(extractor));
+        self
+    }
+
+    pub fn find(&self, path: &Path) -> Option<Arc<dyn Extractor>> {
+        let ext = path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase());
+        // 拡張子のあるファイルは開かない ( 対応していない大きなバイナリなども読まない )
+        if let Some(ext) = ext {
+            return self.extractors.iter().rev().find(|e| e.extensions().contains(&ext.as_str())).cloned();
+        }
+
+        let head = Self::read_head(path)?;
+        self.extractors.iter().rev().find(|e| e.sniff(&head)).cloned()
+    }
+
+    pub fn is_supported(&self, path: &Path) -> bool {
+        self.find(path).is_some()
+    }
+
+    fn read_head(path: &Path) -> Option<Vec<u8>> {
+        let mut head = Vec::with_capacity(SNIFF_BYTES);
+        std::fs::File::open(path).ok()?.take(SNIFF_BYTES as u64).read_to_end(&mut head).ok()?;
+        Some(head)
+    }
+}
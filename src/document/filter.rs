@@ -0,0 +1,261 @@
+use crate::warn;
+use anyhow::{anyhow, Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::path::{Path, PathBuf};
+
+/// `.gitignore` と同じ書式で読み込まないファイルを指定するファイル ( 後のものを優先 )
+pub const IGNORE_FILES: [&str; 2] = [".gitignore", ".ragignore"];
+
+/// 読み込むファイルの絞り込み
+///
+/// 隠しファイル → ignore ファイル → 除外パターン → 対象パターン → 対応形式 → ファイルサイズ の順に判定する
+#[derive(Debug, Clone)]
+pub struct FileFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    max_file_size: Option<u64>,
+    hidden: bool,
+    ignore_files: bool,
+}
+
+/// 読み込まなかったファイル数 ( 除外したディレクトリは中のファイル数によらず 1 件 )
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Skipped {
+    /// ignore ファイルに一致
+    pub ignored: usize,
+    /// 除外パターンに一致、または対象パターンに不一致
+    pub excluded: usize,
+    /// 隠しファイル
+    pub hidden: usize,
+    /// 最大サイズを超えるファイル
+    pub too_large: usize,
+    /// 対応していない形式のファイル
+    pub unsupported: usize,
+}
+
+impl Default for FileFilter {
+    fn default() -> Self {
+        Self { include: None, exclude: GlobSet::empty(), max_file_size: None, hidden: false, ignore_files: true }
+    }
+}
+
+impl FileFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 読み込むファイルの glob パターン ( 対象ディレクトリからの相対パス、空なら全て )
+    pub fn with_include(mut self, patterns: &[String]) -> Result<Self> {
+        self.include = if patterns.is_empty() { None } else { Some(glob_set(patterns)?) };
+        Ok(self)
+    }
+
+    /// 読み込まないファイルやディレクトリの glob パターン ( 対象ディレクトリからの相対パス )
+    pub fn with_exclude(mut self, patterns: &[String]) -> Result<Self> {
+        self.exclude = glob_set(patterns)?;
+        Ok(self)
+    }
+
+    /// 読み込むファイルの最大サイズ ( バイト数 )
+    pub fn with_max_file_size(mut self, max_file_size: Option<u64>) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// 隠しファイル ( `.` で始まるファイルやディレクトリ ) も読み込む ( `.git` は常に読み込まない )
+    pub fn with_hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// ignore ファイル ( `IGNORE_FILES` ) に従う
+    pub fn with_ignore_files(mut self, ignore_files: bool) -> Self {
+        self.ignore_files = ignore_files;
+        self
+    }
+
+    /// root 以下の読み込むファイル ( ファイル名順 )
+    pub fn walk(&self, root: &Path, is_supported: impl Fn(&Path) -> bool) -> Result<(Vec<PathBuf>, Skipped)> {
+        let mut files = vec![];
+        let mut skipped = Skipped::default();
+        // 祖先のディレクトリの ignore ファイル ( 深い方を優先 )
+        let mut ignores: Vec<(usize, Gitignore)> = vec![];
+
+        let mut entries = walkdir::WalkDir::new(root).sort_by_file_name().into_iter();
+        while let Some(entry) = entries.next() {
+            let entry = entry?;
+            let path = entry.path();
+            let is_dir = entry.file_type().is_dir();
+            ignores.retain(|(depth, _)| *depth < entry.depth());
+
+            let relative = path.strip_prefix(root).unwrap_or(path).to_string_lossy().to_string();
+            if entry.depth() > 0 {
+                let name = entry.file_name().to_string_lossy();
+                let count = if is_dir && name == ".git" {
+                    Some(&mut skipped.ignored)
+                } else if !self.hidden && name.starts_with('.') {
+                    Some(&mut skipped.hidden)
+                } else if is_ignored(&ignores, path, is_dir) {
+                    Some(&mut skipped.ignored)
+                } else if self.exclude.is_match(&relative) {
+                    Some(&mut skipped.excluded)
+                } else {
+                    None
+                };
+                if let Some(count) = count {
+                    *count += 1;
+                    if is_dir {
+                        entries.skip_current_dir();
+                    }
+                    continue;
+                }
+            }
+
+            if is_dir {
+                if self.ignore_files {
+                    ignores.extend(read_ignore_files(path).map(|ignore| (entry.depth(), ignore)));
+                }
+                continue;
+            }
+            if !path.is_file() {
+                continue;
+            }
+
+            if self.include.as_ref().is_some_and(|include| !include.is_match(&relative)) {
+                skipped.excluded += 1;
+            } else if !is_supported(path) {
+                skipped.unsupported += 1;
+            } else if self.max_file_size.is_some_and(|max| std::fs::metadata(path).is_ok_and(|m| m.len() > max)) {
+                skipped.too_large += 1;
+            } else {
+                files.push(path.to_path_buf());
+            }
+        }
+
+        Ok((files, skipped))
+    }
+}
+
+impl Skipped {
+    pub fn total(&self) -> usize {
+        self.ignored + self.excluded + self.hidden + self.too_large + self.unsupported
+    }
+}
+
+/// `500K` `20M` `1G` のような単位付きのサイズ ( 単位なしはバイト数 )
+pub fn parse_size(size: &str) -> Result<u64> {
+    let size = size.trim();
+    let upper = size.to_uppercase();
+    let digits = upper.trim_end_matches('B');
+    let (number, unit) = match digits.char_indices().last() {
+        Some((i, 'K')) => (&digits[..i], 1 << 10),
+        Some((i, 'M')) => (&digits[..i], 1 << 20),
+        Some((i, 'G')) => (&digits[..i], 1 << 30),
+        _ => (digits, 1),
+    };
+    let number: u64 = number.trim().parse().map_err(|_| anyhow!("Invalid size: {}", size))?;
+    number.checked_mul(unit).ok_or_else(|| anyhow!("Invalid size: {}", size))
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).with_context(|| format!("Invalid pattern: {}", pattern))?);
+    }
+    Ok(builder.build()?)
+}
+
+/// 深いディレクトリの ignore ファイルから順に判定し、最初に一致した規則に従う
+fn is_ignored(ignores: &[(usize, Gitignore)], path: &Path, is_dir: bool) -> bool {
+    for (_, ignore) in ignores.iter().rev() {
+        match ignore.matched(path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+        }
+    }
+    false
+}
+
+/// ディレクトリ直下の ignore ファイル ( 読み込めない行は警告して無視する )
+fn read_ignore_files(dir: &Path) -> Option<Gitignore> {
+    let paths: Vec<PathBuf> = IGNORE_FILES.iter().map(|name| dir.join(name)).filter(|path| path.is_file()).collect();
+    if paths.is_empty() {
+        return None;
+    }
+
+    let mut builder = GitignoreBuilder::new(dir);
+    for path in paths {
+        if let Some(e) = builder.add(&path) {
+            warn!("Invalid ignore file: {}: {}", path.display(), e);
+        }
+    }
+    match builder.build() {
+        Ok(ignore) => Some(ignore),
+        Err(e) => {
+            warn!("Invalid ignore file: {}: {}", dir.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn walk() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for (path, content) in [
+            (".gitignore", "build/\n*.log\n!keep.log\n"),
+            (".secret.txt", "hidden"),
+            (".git/config", "git"),
+            ("a.txt", "a"),
+            ("a.bin", "binary"),
+            ("big.txt", "0123456789"),
+            ("keep.log", "kept"),
+            ("debug.log", "ignored"),
+            ("build/out.txt", "ignored"),
+            ("docs/.ragignore", "draft-*.md\n"),
+            ("docs/draft-1.md", "ignored"),
+            ("docs/spec.md", "spec"),
+            ("node_modules/pkg/readme.txt", "excluded"),
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let is_supported = |path: &Path| path.extension().is_some_and(|ext| ext != "bin");
+        let names = |files: Vec<PathBuf>| {
+            files.iter().map(|f| f.strip_prefix(root).unwrap().to_string_lossy().to_string()).collect::<Vec<_>>()
+        };
+
+        let filter = FileFilter::new().with_exclude(&["node_modules".to_string()]).unwrap().with_max_file_size(Some(5));
+        let (files, skipped) = filter.walk(root, is_supported).unwrap();
+        assert_eq!(names(files), vec!["a.txt", "docs/spec.md", "keep.log"]);
+        assert_eq!(skipped, Skipped { ignored: 4, excluded: 1, hidden: 3, too_large: 1, unsupported: 1 });
+
+        let filter = FileFilter::new()
+            .with_include(&["**/*.md".to_string()])
+            .unwrap()
+            .with_hidden(true)
+            .with_ignore_files(false);
+        let (files, skipped) = filter.walk(root, is_supported).unwrap();
+        assert_eq!(names(files), vec!["docs/draft-1.md", "docs/spec.md"]);
+        assert_eq!(skipped.ignored, 1);
+    }
+
+    #[test]
+    fn size() {
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("500K").unwrap(), 500 * 1024);
+        assert_eq!(parse_size("20mb").unwrap(), 20 * 1024 * 1024);
+        assert_eq!(parse_size("1G").unwrap(), 1 << 30);
+        assert!(parse_size("abc").is_err());
+        assert!(parse_size("99999999999G").is_err());
+    }
+}
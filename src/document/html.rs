@@ -0,0 +1,417 @@
+use crate::document::encoding;
+use crate::document::extractor::{Block, ExtractedDocument, Extractor, Outline, Section};
+use crate::document::office::single_line;
+use crate::warn;
+use anyhow::{anyhow, Result};
+use encoding_rs::{Encoding, UTF_8};
+use mail_parser::{MessageParser, MimeHeaders};
+use scraper::{ElementRef, Html, Node, Selector};
+use serde_json::json;
+use std::path::Path;
+
+/// `<meta charset>` を探す先頭のバイト数 ( HTML の仕様と同じ )
+const PRESCAN_BYTES: usize = 1024;
+
+/// 本文として扱わない要素
+const IGNORED_ELEMENTS: [&str; 13] = [
+    "script", "style", "noscript", "template", "iframe", "svg", "canvas", "nav", "aside", "footer", "form", "button",
+    "select",
+];
+
+/// 本文として扱わない要素の role
+const IGNORED_ROLES: [&str; 5] = ["navigation", "banner", "contentinfo", "complementary", "search"];
+
+/// id や class の単語がこの名前で始まる場合に本文として扱わない ( Confluence などのパンくずリストやサイドバー )
+const IGNORED_NAMES: [&str; 5] = ["breadcrumb", "sidebar", "footer", "navbar", "navigation"];
+
+/// 本文を探す要素 ( 一致しない場合は body 全体 )
+const MAIN_SELECTOR: &str = "main, [role=main], #main-content, article";
+
+/// HTML ( .html ) とウェブアーカイブ ( .mhtml ) の本文
+///
+/// スクリプトやナビゲーション、フッターなどを除き、見出しごとに区切る。
+/// ページのタイトル ( `title` ) と正規 URL ( `url` ) をメタデータに記録する
+pub struct HtmlExtractor;
+
+impl Extractor for HtmlExtractor {
+    fn name(&self) -> &str {
+        "html"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["html", "htm", "xhtml", "mht", "mhtml"]
+    }
+
+    fn sniff(&self, head: &[u8]) -> bool {
+        let head = String::from_utf8_lossy(head).trim_start_matches('\u{feff}').trim_start().to_lowercase();
+        head.starts_with("<!doctype html") || head.starts_with("<html")
+    }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDocument> {
+        let bytes = std::fs::read(path)?;
+        let is_archive = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("mht") || ext.eq_ignore_ascii_case("mhtml"));
+        let (html, encoding, location) = if is_archive {
+            read_archive(&bytes)?
+        } else {
+            let (html, encoding) = decode(&bytes)?;
+            (html, encoding, None)
+        };
+
+        let page = Page::parse(&html);
+        let mut document =
+            ExtractedDocument { sections: page.sections }.with_attribute("encoding", json!(encoding.name()));
+        if let Some(title) = page.title {
+            document = document.with_attribute("title", json!(title));
+        }
+        if let Some(url) = page.url.or(location) {
+            document = document.with_attribute("url", json!(url));
+        }
+        Ok(document)
+    }
+}
+
+/// ページの本文とメタデータ
+#[derive(Debug, Default)]
+pub struct Page {
+    pub title: Option<String>,
+    /// `<link rel="canonical">` または `<meta property="og:url">` の URL
+    pub url: Option<String>,
+    pub sections: Vec<Section>,
+}
+
+impl Page {
+    pub fn parse(html: &str) -> Self {
+        let document = Html::parse_document(html);
+        let select = |selector: &str| document.select(&Selector::parse(selector).unwrap()).next();
+
+        let title =
+            select("title").map(|title| single_line(&title.text().collect::<String>())).filter(|t| !t.is_empty());
+        let url = select("link[rel~=canonical][href]")
+            .and_then(|link| link.attr("href"))
+            .or_else(|| select("meta[property='og:url'][content]").and_then(|meta| meta.attr("content")))
+            .map(|url| url.trim().to_string());
+
+        let mut walker = Walker::default();
+        if let Some(root) = select(MAIN_SELECTOR).or_else(|| select("body")) {
+            walker.visit(root);
+        }
+        walker.flush();
+        Self { title, url, sections: walker.outline.finish() }
+    }
+}
+
+/// 要素を順にたどり、段落・見出し・表・整形済みテキストに分ける
+#[derive(Default)]
+struct Walker {
+    outline: Outline,
+    /// 段落の途中のテキスト
+    inline: String,
+}
+
+impl Walker {
+    fn visit(&mut self, element: ElementRef) {
+        for child in element.children() {
+            let element = match child.value() {
+                Node::Text(text) => {
+                    self.push_text(text);
+                    continue;
+                }
+                Node::Element(_) => ElementRef::wrap(child).unwrap(),
+                _ => continue,
+            };
+            if is_ignored(element) {
+                continue;
+            }
+
+            let name = element.value().name();
+            match name {
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                    self.flush();
+                    let text = single_line(&element.text().collect::<String>());
+                    if !text.is_empty() {
+                        self.outline.heading(name[1..].parse().unwrap(), text);
+                    }
+                }
+                "table" => {
+                    self.flush();
+                    let rows = table_rows(element);
+                    if !rows.is_empty() {
+                        self.outline.push(Block::Code { code: rows.join("\n"), fence: None });
+                    }
+                }
+                "pre" => {
+                    self.flush();
+                    let code = element.text().collect::<String>();
+                    if !code.trim().is_empty() {
+                        self.outline.push(Block::Code { code: code.trim_matches('\n').to_string(), fence: None });
+                    }
+                }
+                "br" => self.inline.push('\n'),
+                "li" => {
+                    self.flush();
+                    self.inline.push_str("- ");
+                    self.visit(element);
+                    self.flush();
+                }
+                _ if is_block(name) => {
+                    self.flush();
+                    self.visit(element);
+                    self.flush();
+                }
+                _ => self.visit(element),
+            }
+        }
+    }
+
+    /// 連続する空白は 1 つにまとめる
+    fn push_text(&mut self, text: &str) {
+        for c in text.chars() {
+            if !c.is_whitespace() {
+                self.inline.push(c);
+            } else if !self.inline.is_empty() && !self.inline.ends_with([' ', '\n']) {
+                self.inline.push(' ');
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        let lines: Vec<&str> =
+            self.inline.lines().map(str::trim).filter(|line| !line.is_empty() && *line != "-").collect();
+        if !lines.is_empty() {
+            self.outline.push(Block::Text(lines.join("\n")));
+        }
+        self.inline.clear();
+    }
+}
+
+/// 前後で段落を区切る要素
+fn is_block(name: &str) -> bool {
+    matches!(
+        name,
+        "address"
+            | "article"
+            | "blockquote"
+            | "caption"
+            | "dd"
+            | "details"
+            | "div"
+            | "dl"
+            | "dt"
+            | "fieldset"
+            | "figcaption"
+            | "figure"
+            | "header"
+            | "hr"
+            | "main"
+            | "ol"
+            | "p"
+            | "section"
+            | "summary"
+            | "ul"
+    )
+}
+
+/// スクリプトやナビゲーションなど本文ではない要素 ( 記事内の header は本文として扱う )
+fn is_ignored(element: ElementRef) -> bool {
+    let value = element.value();
+    let name = value.name();
+    if IGNORED_ELEMENTS.contains(&name) || value.attr("hidden").is_some() || value.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    if name == "header"
+        && !element.ancestors().filter_map(ElementRef::wrap).any(|a| matches!(a.value().name(), "article" | "main"))
+    {
+        return true;
+    }
+    if value.attr("role").is_some_and(|role| IGNORED_ROLES.contains(&role)) {
+        return true;
+    }
+    [value.attr("id"), value.attr("class")].into_iter().flatten().flat_map(str::split_whitespace).any(is_ignored_name)
+}
+
+/// 単語の先頭の部分 ( `-` / `_` 区切り ) が一致するか ( e.g. `breadcrumbs`, `sidebar-left` は一致、`with-sidebar` は不一致 )
+fn is_ignored_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    let head = name.split(['-', '_']).next().unwrap_or_default();
+    IGNORED_NAMES.iter().any(|ignored| head == *ignored || head.strip_suffix('s') == Some(ignored))
+}
+
+/// 表の各行を `セル | セル` の 1 行にまとめる ( 入れ子の表はセル内のテキストとしてまとめる )
+fn table_rows(table: ElementRef) -> Vec<String> {
+    table
+        .descendent_elements()
+        .filter(|row| row.value().name() == "tr")
+        .filter(|row| {
+            row.ancestors().filter_map(ElementRef::wrap).find(|a| a.value().name() == "table").map(|t| t.id())
+                == Some(table.id())
+        })
+        .map(|row| {
+            row.child_elements()
+                .filter(|cell| matches!(cell.value().name(), "th" | "td"))
+                .map(|cell| single_line(&cell.text().collect::<String>()))
+                .collect::<Vec<_>>()
+        })
+        .filter(|cells| cells.iter().any(|cell| !cell.is_empty()))
+        .map(|cells| cells.join(" | "))
+        .collect()
+}
+
+/// BOM、`<meta charset>`、内容からの判定の順に文字コードを決める
+pub fn decode(bytes: &[u8]) -> Result<(String, &'static Encoding)> {
+    if Encoding::for_bom(bytes).is_some() {
+        return encoding::decode(bytes, None);
+    }
+    match meta_charset(bytes) {
+        Some(encoding) => {
+            let (html, _, had_errors) = encoding.decode(bytes);
+            if had_errors {
+                warn!("Invalid characters are replaced as {}", encoding.name());
+            }
+            Ok((html.into_owned(), encoding))
+        }
+        None => encoding::decode(bytes, None),
+    }
+}
+
+/// 先頭にある `<meta charset="...">` または `<meta http-equiv="Content-Type" content="...; charset=...">` の文字コード
+fn meta_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(PRESCAN_BYTES)]).to_lowercase();
+    for meta in head.split("<meta").skip(1) {
+        let meta = &meta[..meta.find('>').unwrap_or(meta.len())];
+        let Some(start) = meta.find("charset=") else {
+            continue;
+        };
+        let label = meta[start + "charset=".len()..].trim_start_matches(['"', '\'', ' ']);
+        let label = &label[..label.find(['"', '\'', ';', ' ', '/', '>']).unwrap_or(label.len())];
+        // ASCII と互換のない UTF-16 は、meta を読めた時点で誤りなので UTF-8 とみなす
+        return Encoding::for_label(label.as_bytes()).map(Encoding::output_encoding);
+    }
+    None
+}
+
+/// ウェブアーカイブ ( MIME 形式 ) の最初の HTML と文字コード、保存元の URL
+fn read_archive(bytes: &[u8]) -> Result<(String, &'static Encoding, Option<String>)> {
+    let message = MessageParser::default().parse(bytes).ok_or_else(|| anyhow!("Invalid web archive"))?;
+    let part = message.html_part(0).ok_or_else(|| anyhow!("No HTML in web archive"))?;
+    let html = part.text_contents().unwrap_or_default().to_string();
+    let encoding = part
+        .content_type()
+        .and_then(|content_type| content_type.attribute("charset"))
+        .and_then(|charset| Encoding::for_label(charset.as_bytes()))
+        .unwrap_or(UTF_8);
+    let location = part
+        .content_location()
+        .or_else(|| message.header_raw("Snapshot-Content-Location"))
+        .map(|location| location.trim().to_string());
+    Ok((html, encoding, location))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::SHIFT_JIS;
+
+    #[test]
+    fn page() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("page.html");
+        let html = r#"<!DOCTYPE html><html><head>
+            <meta http-equiv="Content-Type" content="text/html; charset=Shift_JIS">
+            <title> API 仕様 - Wiki </title>
+            <link rel="canonical" href="https://wiki.example.com/display/API">
+            <script>var x = "除外";</script>
+        </head><body>
+            <nav><a href="/">ホーム</a></nav>
+            <div id="breadcrumb-section">スペース &gt; API</div>
+            <div id="main-content">
+                <p>前書き<br>2 行目</p>
+                <h1>概要</h1>
+                <ul><li>サーバー</li><li><b>クライアント</b> と <i>ライブラリ</i></li></ul>
+                <h2>認証</h2>
+                <table><tr><th>項目</th><th>値</th></tr><tr><td>token</td><td><table><tr><td>必須</td></tr></table></td></tr></table>
+                <pre>
+fn main() {
+    println!();
+}</pre>
+                <div hidden>非表示</div>
+            </div>
+            <footer>Copyright</footer>
+        </body></html>"#;
+        std::fs::write(&path, SHIFT_JIS.encode(html).0).unwrap();
+
+        let document = HtmlExtractor.extract(&path).unwrap();
+        let sections = &document.sections;
+        for section in sections {
+            assert_eq!(section.attributes.get("title"), Some(&json!("API 仕様 - Wiki")));
+            assert_eq!(section.attributes.get("url"), Some(&json!("https://wiki.example.com/display/API")));
+            assert_eq!(section.attributes.get("encoding"), Some(&json!("Shift_JIS")));
+        }
+        let headings: Vec<Option<&str>> =
+            sections.iter().map(|s| s.attributes.get("heading").and_then(|h| h.as_str())).collect();
+        assert_eq!(headings, vec![None, Some("# 概要"), Some("# 概要 > ## 認証")]);
+        assert_eq!(sections[0].blocks, vec![Block::Text("前書き\n2 行目".to_string())]);
+        assert_eq!(
+            sections[1].blocks[1..],
+            [Block::Text("- サーバー".to_string()), Block::Text("- クライアント と ライブラリ".to_string())]
+        );
+        assert_eq!(
+            sections[2].blocks[1..],
+            [
+                Block::Code { code: "項目 | 値\ntoken | 必須".to_string(), fence: None },
+                Block::Code { code: "fn main() {\n    println!();\n}".to_string(), fence: None },
+            ]
+        );
+    }
+
+    #[test]
+    fn ignored_names() {
+        let html = r#"<body>
+            <div class="content with-sidebar"><p>本文</p></div>
+            <div id="page-footer-aware"><p>続き</p></div>
+            <div class="sidebar-left">サイドバー</div><div class="Breadcrumbs">パンくず</div>
+        </body>"#;
+        let sections = Page::parse(html).sections;
+        assert_eq!(sections[0].blocks, vec![Block::Text("本文".to_string()), Block::Text("続き".to_string())]);
+    }
+
+    #[test]
+    fn archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("page.mhtml");
+        let archive = [
+            "From: <Saved by Blink>",
+            "Snapshot-Content-Location: https://wiki.example.com/pages/1",
+            "Subject: =?utf-8?Q?Page?=",
+            "MIME-Version: 1.0",
+            "Content-Type: multipart/related; type=\"text/html\"; boundary=\"----boundary\"",
+            "",
+            "------boundary",
+            "Content-Type: text/html; charset=\"utf-8\"",
+            "Content-Transfer-Encoding: quoted-printable",
+            "Content-Location: https://wiki.example.com/pages/1",
+            "",
+            "<html><head><title>=E8=A8=AD=E8=A8=88</title></head><body><h1>=E6=A6=82=E8=A6=81</h1><p>=E6=9C=AC=E6=96=87=",
+            "</p></body></html>",
+            "------boundary",
+            "Content-Type: image/png",
+            "Content-Transfer-Encoding: base64",
+            "Content-Location: https://wiki.example.com/logo.png",
+            "",
+            "iVBORw0KGgo=",
+            "------boundary--",
+            "",
+        ]
+        .join("\r\n");
+        std::fs::write(&path, archive).unwrap();
+
+        let sections = HtmlExtractor.extract(&path).unwrap().sections;
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].blocks, vec![Block::Text("概要".to_string()), Block::Text("本文".to_string())]);
+        assert_eq!(sections[0].attributes.get("title"), Some(&json!("設計")));
+        assert_eq!(sections[0].attributes.get("url"), Some(&json!("https://wiki.example.com/pages/1")));
+        assert!(HtmlExtractor.sniff(b"\n<!DOCTYPE html>"));
+    }
+}
@@ -0,0 +1,274 @@
+use crate::chroma::document::Attributes;
+use crate::document::extractor::{Block, ExtractedDocument, Extractor, ExtractorRegistry, Section};
+use crate::warn;
+use anyhow::{anyhow, Result};
+use mail_parser::mailbox::mbox::MessageIterator;
+use mail_parser::{Address, Message, MessageParser, MimeHeaders};
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// メールファイルの先頭に現れるヘッダー ( 拡張子のないファイルの判定に使う )
+const LEADING_HEADERS: [&str; 5] = ["Return-Path:", "Received:", "Delivered-To:", "Message-ID:", "MIME-Version:"];
+
+/// 本文の先頭に付ける見出しとメタデータのキー
+const HEADER_LINES: [(&str, &str); 5] =
+    [("From", "from"), ("To", "to"), ("Cc", "cc"), ("Date", "date"), ("Subject", "subject")];
+
+/// メール ( .eml ) とメールボックス ( .mbox )
+///
+/// 1 通ごとに区切り、差出人・宛先・日時・件名・Message-ID・スレッドの ID をメタデータに記録する。
+/// 本文の引用行 ( `>` で始まる行 ) は除き、添付ファイルは対応する Extractor で読み込む
+pub struct MailExtractor {
+    /// 添付ファイルに使う Extractor
+    attachments: ExtractorRegistry,
+}
+
+impl MailExtractor {
+    pub fn new(attachments: ExtractorRegistry) -> Self {
+        Self { attachments }
+    }
+
+    fn parse(&self, raw: &[u8]) -> Result<Vec<Section>> {
+        let message = MessageParser::default().parse(raw).ok_or_else(|| anyhow!("Invalid mail"))?;
+        self.message_sections(&message)
+    }
+
+    fn message_sections(&self, message: &Message) -> Result<Vec<Section>> {
+        let attributes = headers(message);
+
+        let header: Vec<String> = HEADER_LINES
+            .iter()
+            .filter_map(|(name, key)| Some(format!("{}: {}", name, attributes.get(*key)?.as_str()?)))
+            .collect();
+        let mut blocks = vec![];
+        if !header.is_empty() {
+            blocks.push(Block::Text(header.join("\n")));
+        }
+        let body = body(message);
+        if !body.is_empty() {
+            blocks.push(Block::Text(body));
+        }
+        let mut sections = vec![Section { blocks, attributes: attributes.clone() }];
+
+        if message.attachment_count() == 0 {
+            return Ok(sections);
+        }
+        let dir = tempfile::tempdir()?;
+        for part in message.attachments() {
+            // 転送されたメールは解析済みのメールをそのまま読み込む
+            let (name, extracted) = match (part.message(), part.attachment_name()) {
+                (Some(forwarded), name) => {
+                    let name = name.or(forwarded.subject()).unwrap_or_default().to_string();
+                    (name, self.message_sections(forwarded))
+                }
+                (None, Some(name)) => {
+                    let Some(file_name) = Path::new(name).file_name() else { continue };
+                    let path = dir.path().join(file_name);
+                    std::fs::write(&path, part.contents())?;
+                    let Some(extractor) = self.attachments.find(&path) else { continue };
+                    (name.to_string(), extractor.extract(&path).map(|document| document.sections))
+                }
+                // 名前のない画像などは読み込まない
+                (None, None) => continue,
+            };
+
+            let extracted = match extracted {
+                Ok(extracted) => extracted,
+                Err(e) => {
+                    warn!("Failed to extract attachment: {}: {}", name, e);
+                    continue;
+                }
+            };
+            for mut section in extracted {
+                for (key, value) in &attributes {
+                    section.attributes.entry(key.clone()).or_insert_with(|| value.clone());
+                }
+                section.attributes.insert("attachment".to_string(), json!(name));
+                sections.push(section);
+            }
+        }
+        Ok(sections)
+    }
+}
+
+impl Extractor for MailExtractor {
+    fn name(&self) -> &str {
+        "mail"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["eml", "mbox"]
+    }
+
+    fn sniff(&self, head: &[u8]) -> bool {
+        let mut lines = head.split(|b| *b == b'\n');
+        let first = lines.next().unwrap_or_default();
+        if first.starts_with(b"From ") {
+            return lines.next().is_some_and(is_header);
+        }
+        LEADING_HEADERS
+            .iter()
+            .any(|header| first.get(..header.len()).is_some_and(|h| h.eq_ignore_ascii_case(header.as_bytes())))
+    }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDocument> {
+        let mut reader = BufReader::new(File::open(path)?);
+        // mbox は `From ` で始まる行ごとに 1 通 ( 大きなメールボックスも全体を読み込まずに 1 通ずつ読む )
+        if !reader.fill_buf()?.starts_with(b"From ") {
+            let mut bytes = vec![];
+            reader.read_to_end(&mut bytes)?;
+            return Ok(ExtractedDocument { sections: self.parse(&bytes)? });
+        }
+        // 読み込めないメールがあっても他のメールは読み込む
+        let mut sections = vec![];
+        for (i, message) in MessageIterator::new(reader).enumerate() {
+            match message.map_err(anyhow::Error::from).and_then(|message| self.parse(message.contents())) {
+                Ok(message_sections) => sections.extend(message_sections),
+                Err(e) => warn!("Skipped message {}: {}: {}", i + 1, path.display(), e),
+            }
+        }
+        Ok(ExtractedDocument { sections })
+    }
+}
+
+/// ヘッダーのメタデータ ( Message-ID は `<>` を除く )
+fn headers(message: &Message) -> Attributes {
+    let mut attributes = Attributes::new();
+    let mut insert = |key: &str, value: Option<String>| {
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            attributes.insert(key.to_string(), Value::String(value));
+        }
+    };
+    insert("from", message.from().map(addresses));
+    insert("to", message.to().map(addresses));
+    insert("cc", message.cc().map(addresses));
+    insert("date", message.date().map(|date| date.to_rfc3339()));
+    insert("subject", message.subject().map(str::to_string));
+    insert("message_id", message.message_id().map(str::to_string));
+
+    // スレッドの最初のメール ( References の先頭、なければ返信先、どちらもなければ自身 ) の Message-ID
+    let first = |ids: Option<&[std::borrow::Cow<str>]>| ids.and_then(|ids| ids.first()).map(|id| id.to_string());
+    let thread_id = first(message.references().as_text_list())
+        .or_else(|| first(message.in_reply_to().as_text_list()))
+        .or_else(|| message.message_id().map(str::to_string));
+    insert("thread_id", thread_id);
+    attributes
+}
+
+/// `名前 <アドレス>` をカンマで区切った文字列
+fn addresses(address: &Address) -> String {
+    let addresses: Vec<String> = address
+        .iter()
+        .filter_map(|addr| match (addr.name(), addr.address()) {
+            (Some(name), Some(address)) => Some(format!("{} <{}>", name, address)),
+            (name, address) => address.or(name).map(str::to_string),
+        })
+        .collect();
+    addresses.join(", ")
+}
+
+/// テキストの本文 ( HTML のみの場合はテキストに変換する ) から引用行を除いたもの
+fn body(message: &Message) -> String {
+    let text: Vec<String> = (0..message.text_body_count())
+        .filter_map(|i| message.body_text(i))
+        .map(|text| text.lines().filter(|line| !line.trim_start().starts_with('>')).collect::<Vec<_>>().join("\n"))
+        .collect();
+    text.join("\n\n").trim().to_string()
+}
+
+/// `Name: value` 形式のヘッダーの行
+fn is_header(line: &[u8]) -> bool {
+    match line.iter().position(|b| *b == b':') {
+        Some(colon) => colon > 0 && line[..colon].iter().all(|b| b.is_ascii_alphanumeric() || *b == b'-'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::ISO_2022_JP;
+
+    #[test]
+    fn forwarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fwd.eml");
+        let eml = "From: sato@example.com\r\nSubject: Fwd: TTL\r\nMessage-ID: <10@example.com>\r\n\
+            MIME-Version: 1.0\r\nContent-Type: multipart/mixed; boundary=\"b\"\r\n\r\n\
+            --b\r\nContent-Type: text/plain\r\n\r\n転送します\r\n\
+            --b\r\nContent-Type: message/rfc822\r\n\r\n\
+            From: tanaka@example.com\r\nSubject: TTL\r\nMessage-ID: <1@example.com>\r\n\r\n24時間にします\r\n\
+            --b--\r\n";
+        std::fs::write(&path, eml).unwrap();
+
+        // 転送されたメールはメールの Extractor がない一覧でも読み込む
+        let sections = MailExtractor::new(ExtractorRegistry::new()).extract(&path).unwrap().sections;
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[1].blocks[1], Block::Text("24時間にします".to_string()));
+        assert_eq!(sections[1].attributes.get("attachment"), Some(&json!("TTL")));
+        assert_eq!(sections[1].attributes.get("message_id"), Some(&json!("1@example.com")));
+
+        // 見出しにするヘッダーがないメールは本文のみ
+        let path = dir.path().join("note.eml");
+        std::fs::write(&path, "Content-Type: text/plain; charset=utf-8\r\n\r\nメモ\r\n").unwrap();
+        let sections = MailExtractor::new(ExtractorRegistry::new()).extract(&path).unwrap().sections;
+        assert_eq!(sections[0].blocks, vec![Block::Text("メモ".to_string())]);
+    }
+
+    #[test]
+    fn mbox() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("project.mbox");
+        let mut mbox = b"From tanaka@example.com Wed May  1 10:00:00 2024\r\n\
+            From: =?ISO-2022-JP?B?GyRCRURDZhsoQg==?= <tanaka@example.com>\r\n\
+            To: dev@example.com\r\n\
+            Date: Wed, 1 May 2024 10:00:00 +0900\r\n\
+            Subject: =?ISO-2022-JP?B?GyRCO0VNTSRON2hEahsoQg==?=\r\n\
+            Message-ID: <2@example.com>\r\n\
+            In-Reply-To: <1@example.com>\r\n\
+            References: <1@example.com>\r\n\
+            MIME-Version: 1.0\r\n\
+            Content-Type: multipart/mixed; boundary=\"b\"\r\n\r\n\
+            --b\r\nContent-Type: text/plain; charset=ISO-2022-JP\r\nContent-Transfer-Encoding: 7bit\r\n\r\n"
+            .to_vec();
+        mbox.extend_from_slice(&ISO_2022_JP.encode("> 何時間にしますか？\r\nキャッシュは24時間にします\r\n").0);
+        mbox.extend_from_slice(
+            b"--b\r\nContent-Type: text/markdown\r\nContent-Disposition: attachment; filename=\"spec.md\"\r\n\r\n\
+            # TTL\r\n\r\n24h\r\n\
+            --b\r\nContent-Type: image/png\r\nContent-Disposition: inline\r\n\r\nPNG\r\n--b--\r\n\r\n\
+            From broken@example.com Wed May  1 11:00:00 2024\r\n\
+            From sato@example.com Thu May  2 09:00:00 2024\r\n\
+            From: sato@example.com\r\nSubject: Re\r\nMessage-ID: <3@example.com>\r\n\r\n\
+            >From the log, OK\r\n",
+        );
+        std::fs::write(&path, mbox).unwrap();
+
+        // 読み込めない ( 空の ) メールは飛ばす
+        let sections = MailExtractor::new(ExtractorRegistry::with_defaults()).extract(&path).unwrap().sections;
+        assert_eq!(sections.len(), 3);
+        let header = "From: 田中 <tanaka@example.com>\nTo: dev@example.com\nDate: 2024-05-01T10:00:00+09:00\nSubject: 仕様の決定";
+        assert_eq!(
+            sections[0].blocks,
+            vec![Block::Text(header.to_string()), Block::Text("キャッシュは24時間にします".to_string())]
+        );
+        let attributes = &sections[0].attributes;
+        assert_eq!(attributes.get("message_id"), Some(&json!("2@example.com")));
+        assert_eq!(attributes.get("thread_id"), Some(&json!("1@example.com")));
+
+        // 添付ファイルはメールのメタデータを引き継ぐ
+        assert_eq!(sections[1].attributes.get("attachment"), Some(&json!("spec.md")));
+        assert_eq!(sections[1].attributes.get("heading"), Some(&json!("# TTL")));
+        assert_eq!(sections[1].attributes.get("thread_id"), Some(&json!("1@example.com")));
+
+        // `>From` は mbox の区切りと区別するための引用で、本文の引用行ではない
+        assert_eq!(sections[2].blocks[1], Block::Text("From the log, OK".to_string()));
+        assert_eq!(sections[2].attributes.get("thread_id"), Some(&json!("3@example.com")));
+
+        let extractor = MailExtractor::new(ExtractorRegistry::new());
+        assert!(extractor.sniff(b"From a@example.com Wed May  1 10:00:00 2024\nReturn-Path: <a@example.com>\n"));
+        assert!(extractor.sniff(b"Received: from mx.example.com\n"));
+        assert!(!extractor.sniff(b"From now on\nwe use TTL 24h\n"));
+    }
+}
@@ -1,7 +1,205 @@
+use crate::document::encoding;
+use crate::document::extractor::{Block, ExtractedDocument, Extractor, Outline, Section};
 use anyhow::Result;
-use std::fs;
+use encoding_rs::Encoding;
+use markdown::Span;
+use serde_json::json;
 use std::path::Path;
 
+#[derive(Default)]
+pub struct MarkdownExtractor {
+    /// 文字コード ( 指定しない場合は自動判定 )
+    encoding: Option<&'static Encoding>,
+}
+
+impl MarkdownExtractor {
+    pub fn new(encoding: Option<&'static Encoding>) -> Self {
+        Self { encoding }
+    }
+}
+
+impl Extractor for MarkdownExtractor {
+    fn name(&self) -> &str {
+        "markdown"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["md"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDocument> {
+        let (text, encoding) = encoding::read_text(path, self.encoding)?;
+        Ok(ExtractedDocument { sections: parse_sections(&text) }.with_attribute("encoding", json!(encoding.name())))
+    }
+}
+
 pub fn extract_text(path: &Path) -> Result<String> {
-    Ok(fs::read_to_string(path)?)
+    Ok(encoding::read_text(path, None)?.0)
+}
+
+/// 見出しごとに区切り、各区切りに見出しの階層 ( e.g. `# API > ## Auth > ### Tokens` ) を付与する
+pub fn parse_sections(text: &str) -> Vec<Section> {
+    let mut parser = Parser::default();
+    let mut lines = text.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if let Some((fence, code)) = &mut parser.fence {
+            if is_closing_fence(line, fence) {
+                let block = Block::Code { code: code.join("\n"), fence: Some(fence.clone()) };
+                parser.fence = None;
+                parser.push(block);
+            } else {
+                code.push(line.to_string());
+            }
+        } else if is_opening_fence(line) {
+            parser.flush_paragraph();
+            parser.fence = Some((line.trim().to_string(), vec![]));
+        } else if let Some(level) = atx_level(line) {
+            parser.flush_paragraph();
+            parser.heading(level, line);
+        } else if let Some(level) = lines.peek().and_then(|next| setext_level(next)).filter(|_| !line.trim().is_empty())
+        {
+            // 段落の最終行が見出しになる
+            parser.flush_paragraph();
+            let underline = lines.next().unwrap_or_default();
+            parser.heading(level, &format!("{}\n{}", line, underline));
+        } else if line.trim().is_empty() {
+            parser.flush_paragraph();
+        } else {
+            parser.paragraph.push(line.to_string());
+        }
+    }
+
+    // 閉じられていないフェンスは終端までをコードとする
+    if let Some((fence, code)) = parser.fence.take() {
+        parser.push(Block::Code { code: code.join("\n"), fence: Some(fence) });
+    }
+    parser.flush_paragraph();
+
+    parser.outline.finish()
+}
+
+#[derive(Default)]
+struct Parser {
+    outline: Outline,
+    paragraph: Vec<String>,
+    /// 開始フェンスとコードの行
+    fence: Option<(String, Vec<String>)>,
+}
+
+impl Parser {
+    fn push(&mut self, block: Block) {
+        self.outline.push(block);
+    }
+
+    fn flush_paragraph(&mut self) {
+        if !self.paragraph.is_empty() {
+            let paragraph = std::mem::take(&mut self.paragraph).join("\n");
+            self.push(Block::Text(paragraph));
+        }
+    }
+
+    fn heading(&mut self, level: usize, raw: &str) {
+        self.outline.heading_with_title(level, heading_text(raw), raw.to_string());
+    }
+}
+
+fn is_opening_fence(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    line.len() - trimmed.len() <= 3 && (trimmed.starts_with("```") || trimmed.starts_with("~~~"))
+}
+
+fn is_closing_fence(line: &str, fence: &str) -> bool {
+    let marker = super::extractor::closing_fence(fence);
+    let trimmed = line.trim();
+    trimmed.starts_with(&marker) && trimmed.chars().all(|c| marker.starts_with(c))
+}
+
+fn atx_level(line: &str) -> Option<usize> {
+    let trimmed = line.trim_start();
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    let rest = &trimmed[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with([' ', '\t']))).then_some(level)
+}
+
+fn setext_level(line: &str) -> Option<usize> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || line.len() - line.trim_start().len() > 3 {
+        None
+    } else if trimmed.chars().all(|c| c == '=') {
+        Some(1)
+    } else if trimmed.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// 見出しの装飾 ( 強調やリンクなど ) を除いたテキスト
+fn heading_text(raw: &str) -> String {
+    let text = match markdown::tokenize(raw).into_iter().next() {
+        Some(markdown::Block::Header(spans, _)) => spans_text(&spans),
+        _ => raw.lines().next().unwrap_or_default().trim_start_matches('#').to_string(),
+    };
+    text.trim().trim_end_matches('#').trim().to_string()
+}
+
+fn spans_text(spans: &[Span]) -> String {
+    spans
+        .iter()
+        .map(|span| match span {
+            Span::Break => " ".to_string(),
+            Span::Text(text) | Span::Code(text) => text.clone(),
+            Span::Link(text, _, _) | Span::Image(text, _, _) => text.clone(),
+            Span::Emphasis(spans) | Span::Strong(spans) => spans_text(spans),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections() {
+        let text = [
+            "前書き",
+            "",
+            "# API",
+            "概要",
+            "",
+            "## **Auth**",
+            "",
+            "```rust",
+            "# not heading",
+            "",
+            "fn main() {}",
+            "```",
+            "",
+            "### Tokens",
+            "トークン",
+            "",
+            "Errors",
+            "------",
+            "エラー",
+        ]
+        .join("\n");
+
+        let sections = parse_sections(&text);
+
+        let headings: Vec<_> =
+            sections.iter().map(|s| s.attributes.get("heading").and_then(|h| h.as_str()).unwrap_or("")).collect();
+        assert_eq!(headings, vec!["", "# API", "# API > ## Auth", "# API > ## Auth > ### Tokens", "# API > ## Errors"]);
+
+        assert_eq!(
+            sections[2].blocks,
+            vec![
+                Block::Text("## **Auth**".to_string()),
+                Block::Code { code: "# not heading\n\nfn main() {}".to_string(), fence: Some("```rust".to_string()) },
+            ]
+        );
+    }
 }
@@ -1,74 +1,216 @@
 use crate::chroma::document::{ChunkMetadata, CollectionName, Document, FileMetadata, Metadata, SearchMetadata};
-use crate::{info, warn};
+use crate::warn;
 use anyhow::{anyhow, Result};
-use chrono::DateTime;
-use std::path::Path;
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
+pub mod code;
+pub mod collection;
+pub mod docx;
+pub mod encoding;
+pub mod extractor;
+pub mod filter;
+pub mod html;
+pub mod mail;
 pub mod markdown;
+pub mod office;
 pub mod pdf;
+pub mod pptx;
+pub mod table;
 pub mod text;
+pub mod tokenizer;
+pub mod xlsx;
 
+pub use collection::CollectionMapper;
+pub use extractor::{Block, ExtractedDocument, Extractor, ExtractorRegistry, Outline, Section};
+pub use filter::{FileFilter, Skipped};
+
+#[derive(Clone)]
 pub struct DocumentProcessor {
     chunk_size: usize,
+    registry: ExtractorRegistry,
+    heading_context: bool,
+    splitter: SplitterKind,
+    max_tokens: Option<usize>,
+    collections: CollectionMapper,
+    filter: FileFilter,
 }
 
 pub type Processed = (Vec<Document>, CollectionName);
 
+#[derive(Debug)]
+pub struct SourceFile {
+    pub full_path: PathBuf,
+    pub path: String,
+    pub updated_at: DateTime<Utc>,
+    pub collection_name: CollectionName,
+    /// ファイルの内容の SHA-256 ( 差分更新の判定と保存で使い回す )
+    pub hash: OnceLock<String>,
+}
+
+impl SourceFile {
+    /// ファイルの内容の SHA-256 ( 最初に必要になった時だけ計算する )
+    pub fn content_hash(&self) -> Result<&str> {
+        if let Some(hash) = self.hash.get() {
+            return Ok(hash);
+        }
+        let hash = crate::sync::file_hash(&self.full_path)?;
+        Ok(self.hash.get_or_init(|| hash))
+    }
+}
+
 impl DocumentProcessor {
     pub fn new(chunk_size: usize) -> Self {
-        Self { chunk_size }
+        Self {
+            chunk_size,
+            registry: ExtractorRegistry::with_defaults(),
+            heading_context: false,
+            splitter: SplitterKind::default(),
+            max_tokens: None,
+            collections: CollectionMapper::default(),
+            filter: FileFilter::default(),
+        }
+    }
+
+    /// 見出しの階層をチャンクの先頭に付与する
+    pub fn with_heading_context(mut self, heading_context: bool) -> Self {
+        self.heading_context = heading_context;
+        self
+    }
+
+    /// 独自の Extractor を登録した一覧を使う
+    pub fn with_registry(mut self, registry: ExtractorRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    pub fn with_splitter(mut self, splitter: SplitterKind) -> Self {
+        self.splitter = splitter;
+        self
+    }
+
+    /// コレクション名の決め方 ( デフォルトは 2 階層までのディレクトリ名 )
+    pub fn with_collections(mut self, collections: CollectionMapper) -> Self {
+        self.collections = collections;
+        self
+    }
+
+    /// 読み込むファイルの絞り込み ( デフォルトは隠しファイルと ignore ファイルに一致するファイルを除く )
+    pub fn with_filter(mut self, filter: FileFilter) -> Self {
+        self.filter = filter;
+        self
     }
 
-    pub async fn process_directory(&self, root_path: &Path) -> Result<Vec<Processed>> {
+    /// チャンクサイズを文字数ではなく近似的なトークン数で指定する
+    pub fn with_max_tokens(mut self, max_tokens: Option<usize>) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    pub fn scan(&self, root_path: &Path) -> Result<Vec<SourceFile>> {
+        Ok(self.scan_with_skipped(root_path)?.0)
+    }
+
+    /// 読み込むファイルと、絞り込みで除いたファイル数
+    pub fn scan_with_skipped(&self, root_path: &Path) -> Result<(Vec<SourceFile>, Skipped)> {
+        let (files, skipped) = self.filter.walk(root_path, |path| self.registry.is_supported(path))?;
+
         let mut result = Vec::new();
-        for entry in walkdir::WalkDir::new(root_path) {
-            let entry = entry?;
-            let full_path = entry.path();
-
-            if full_path.is_file() && Self::is_supported_file(full_path) {
-                result.push(self.process_file(root_path, full_path).await?);
-                info!(
-                    "Converted: {}",
-                    full_path.to_string_lossy().to_string().replace(&format!("{}/", &root_path.to_string_lossy()), "")
-                );
-            }
+        for full_path in files {
+            let path = Self::relative_path(root_path, &full_path);
+            let updated_at = DateTime::from(std::fs::metadata(&full_path)?.modified()?);
+            let collection_name = self.collections.name(&path);
+            result.push(SourceFile { full_path, path, updated_at, collection_name, hash: OnceLock::new() });
         }
-        Ok(result)
+        Ok((result, skipped))
     }
 
-    async fn process_file(&self, root_path: &Path, full_path: &Path) -> Result<Processed> {
-        let content = match full_path.extension().and_then(|ext| ext.to_str()) {
-            Some("txt") => text::extract_text(full_path)?,
-            Some("md") => markdown::extract_text(full_path)?,
-            Some("pdf") => pdf::extract_text(full_path)?,
-            _ => {
-                warn!("Unsupported file type: {}", full_path.display());
-                return Err(anyhow!("unsupported file"));
-            }
+    /// テキスト抽出 ( OCR を含む ) と分割はブロッキング処理用のスレッドで行う
+    pub async fn process_file(&self, root_path: &Path, full_path: &Path) -> Result<Processed> {
+        let processor = self.clone();
+        let (root_path, full_path) = (root_path.to_path_buf(), full_path.to_path_buf());
+        tokio::task::spawn_blocking(move || {
+            let hash = crate::sync::file_hash(&full_path)?;
+            processor.process_file_blocking(&root_path, &full_path, &hash)
+        })
+        .await?
+    }
+
+    /// 走査したファイルを処理する ( 内容のハッシュは計算済みであれば使い回し、未計算であれば計算して記録する )
+    pub async fn process_source(&self, root_path: &Path, source: &SourceFile) -> Result<Processed> {
+        let processor = self.clone();
+        let (root_path, full_path) = (root_path.to_path_buf(), source.full_path.clone());
+        let hash = source.hash.get().cloned();
+        let (processed, hash) = tokio::task::spawn_blocking(move || {
+            let hash = match hash {
+                Some(hash) => hash,
+                None => crate::sync::file_hash(&full_path)?,
+            };
+            Ok::<_, anyhow::Error>((processor.process_file_blocking(&root_path, &full_path, &hash)?, hash))
+        })
+        .await??;
+        source.hash.get_or_init(|| hash);
+        Ok(processed)
+    }
+
+    fn process_file_blocking(&self, root_path: &Path, full_path: &Path, hash: &str) -> Result<Processed> {
+        let Some(extractor) = self.registry.find(full_path) else {
+            warn!("Unsupported file type: {}", full_path.display());
+            return Err(anyhow!("unsupported file"));
         };
+        let extracted = extractor.extract(full_path)?;
 
         let metadata = std::fs::metadata(full_path)?;
 
-        let path = full_path.to_string_lossy().to_string().replace(&format!("{}/", &root_path.to_string_lossy()), "");
-        let created_at = DateTime::from(metadata.created()?);
+        let path = Self::relative_path(root_path, full_path);
         let updated_at = DateTime::from(metadata.modified()?);
+        // 作成日時を取得できないファイルシステムでは更新日時で代用する
+        let created_at = metadata.created().map(DateTime::from).unwrap_or(updated_at);
+
+        // テキスト分割 ( ページや見出しなどの区切りをまたがないように区切りごとに分割する )
+        let (max_size, unit) = match self.max_tokens {
+            Some(max_tokens) => (max_tokens, ChunkUnit::Token),
+            None => (self.chunk_size, ChunkUnit::Char),
+        };
+        let mut chunks = Vec::new();
+        for section in extracted.sections {
+            let heading = section
+                .attributes
+                .get("heading")
+                .and_then(|heading| heading.as_str())
+                .filter(|_| self.heading_context)
+                .map(|heading| heading.to_string());
+
+            // 見出しを付与する分だけチャンクサイズを小さくする ( 0 では分割が進まないため 1 以上にする )
+            let chunk_size = match &heading {
+                Some(heading) => max_size.saturating_sub(unit.measure(&format!("{}\n\n", heading))).max(max_size / 2),
+                None => max_size,
+            }
+            .max(1);
+            let splitter = TextSplitter::new(chunk_size, chunk_size / 10, self.splitter).with_unit(unit);
 
-        // テキスト分割
-        let splitter = TextSplitter::new(self.chunk_size, self.chunk_size / 10);
-        let chunks = splitter.split(&content);
+            for chunk in splitter.split_blocks(&section.blocks) {
+                let content = match &heading {
+                    Some(heading) => format!("{}\n\n{}", heading, chunk),
+                    None => chunk,
+                };
+                chunks.push((content, section.attributes.clone()));
+            }
+        }
 
-        let collection_name = Self::fix_collection_name(&path);
+        let collection_name = self.collections.name(&path);
 
         Ok((
             chunks
                 .into_iter()
                 .enumerate()
-                .map(|(index, chunk)| Document {
+                .map(|(index, (chunk, attributes))| Document {
                     id: format!("{}-{}", path, index),
                     content: chunk,
                     metadata: Metadata {
-                        file: FileMetadata { path: path.clone(), created_at, updated_at },
-                        chunk: ChunkMetadata { index },
+                        file: FileMetadata { path: path.clone(), created_at, updated_at, hash: Some(hash.to_string()) },
+                        chunk: ChunkMetadata { index, attributes },
                         search: SearchMetadata {},
                     },
                 })
@@ -77,46 +219,225 @@ impl DocumentProcessor {
         ))
     }
 
-    fn fix_collection_name(path: &str) -> String {
-        let slash = path.chars().filter(|c| c == &'/').count();
-        if slash == 0 {
-            "root".to_string()
-        } else if slash == 1 {
-            path.split("/").take(1).collect::<Vec<_>>().join("-")
-        } else {
-            path.split("/").take(2).collect::<Vec<_>>().join("-")
+    fn relative_path(root_path: &Path, full_path: &Path) -> String {
+        full_path.to_string_lossy().to_string().replace(&format!("{}/", &root_path.to_string_lossy()), "")
+    }
+}
+
+/// 文の区切りとみなす文字
+const SENTENCE_DELIMITERS: [char; 4] = ['。', '！', '？', '\n'];
+
+/// 文の区切りの直後にあれば直前の文に含める文字
+const SENTENCE_TRAILERS: [char; 9] = ['。', '！', '？', '\n', '」', '』', '）', ')', '"'];
+
+/// チャンクの分割方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SplitterKind {
+    /// 文字数で機械的に分割する
+    #[default]
+    Char,
+    /// 文 ( `。` `！` `？` や改行、段落 ) の境界で分割する
+    Sentence,
+}
+
+/// チャンクサイズの単位
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum ChunkUnit {
+    #[default]
+    Char,
+    /// 近似的なトークン数 ( `tokenizer::tokenize` )
+    Token,
+}
+
+impl ChunkUnit {
+    fn measure(&self, text: &str) -> usize {
+        match self {
+            ChunkUnit::Char => text.chars().count(),
+            ChunkUnit::Token => tokenizer::count_tokens(text),
         }
     }
 
-    fn is_supported_file(path: &Path) -> bool {
-        if let Some(ext) = path.extension() {
-            if let Some(ext_str) = ext.to_str() {
-                return matches!(ext_str.to_lowercase().as_str(), "txt" | "pdf" | "md");
-            }
+    /// 単位ごとの断片 ( 連結すると元のテキストになる )
+    fn units<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        match self {
+            ChunkUnit::Char => text.char_indices().map(|(i, c)| &text[i..i + c.len_utf8()]).collect(),
+            ChunkUnit::Token => tokenizer::tokenize(text),
         }
-        false
     }
 }
 
 struct TextSplitter {
     chunk_size: usize,
     chunk_overlap: usize,
+    kind: SplitterKind,
+    unit: ChunkUnit,
 }
 
 impl TextSplitter {
-    fn new(chunk_size: usize, chunk_overlap: usize) -> Self {
-        Self { chunk_size, chunk_overlap }
+    fn new(chunk_size: usize, chunk_overlap: usize, kind: SplitterKind) -> Self {
+        assert!(chunk_size > chunk_overlap, "chunk_size must be greater than chunk_overlap");
+        Self { chunk_size, chunk_overlap, kind, unit: ChunkUnit::default() }
+    }
+
+    fn with_unit(mut self, unit: ChunkUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// まとまりの境界を優先して分割し、チャンクサイズを超えるまとまりのみ内部で分割する
+    fn split_blocks(&self, blocks: &[Block]) -> Vec<String> {
+        let mut chunks = Vec::new();
+        let mut current = String::new();
+
+        for block in blocks {
+            let text = block.render();
+            let size = self.unit.measure(&text);
+
+            if size > self.chunk_size {
+                if !current.is_empty() {
+                    chunks.push(std::mem::take(&mut current));
+                }
+                match block {
+                    Block::Text(text) => chunks.extend(self.split(text)),
+                    Block::Code { code, fence } => chunks.extend(self.split_code(code, fence.as_deref())),
+                }
+            } else if !current.is_empty()
+                && self.unit.measure(&current) + self.unit.measure("\n\n") + size > self.chunk_size
+            {
+                chunks.push(std::mem::replace(&mut current, text));
+            } else {
+                if !current.is_empty() {
+                    current.push_str("\n\n");
+                }
+                current.push_str(&text);
+            }
+        }
+
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+        chunks
+    }
+
+    /// 行単位で分割し、フェンスがある場合は各チャンクをフェンスで囲む
+    fn split_code(&self, code: &str, fence: Option<&str>) -> Vec<String> {
+        let wrap = |body: &str| match fence {
+            Some(fence) => format!("{}\n{}\n{}", fence, body, extractor::closing_fence(fence)),
+            None => body.to_string(),
+        };
+        let budget = self.chunk_size.saturating_sub(self.unit.measure(&wrap(""))).max(1);
+
+        let mut chunks = Vec::new();
+        let mut current: Vec<&str> = Vec::new();
+        let mut current_size = 0;
+
+        for line in code.lines() {
+            let size = self.unit.measure(line);
+            if !current.is_empty() && current_size + 1 + size > budget {
+                chunks.push(wrap(&current.join("\n")));
+                current.clear();
+                current_size = 0;
+            }
+
+            if size > budget {
+                // 1 行でチャンクサイズを超える場合のみ行の途中で分割する
+                chunks.extend(self.unit.units(line).chunks(budget).map(|part| wrap(&part.concat())));
+            } else {
+                current_size += if current.is_empty() { size } else { size + 1 };
+                current.push(line);
+            }
+        }
+
+        if !current.is_empty() {
+            chunks.push(wrap(&current.join("\n")));
+        }
+        chunks
     }
 
     fn split(&self, text: &str) -> Vec<String> {
-        let chars: Vec<char> = text.chars().collect();
+        match self.kind {
+            SplitterKind::Char => self.split_chars(text),
+            SplitterKind::Sentence => self.split_sentences(text),
+        }
+    }
+
+    /// 文をチャンクサイズまで詰め、チャンクサイズを超える文のみ文字数で分割する
+    ///
+    /// オーバーラップは直前のチャンク末尾の文単位で引き継ぐ
+    fn split_sentences(&self, text: &str) -> Vec<String> {
+        let sentences = sentences(text);
+
+        let mut chunks = Vec::new();
+        let mut current: Vec<&str> = Vec::new();
+        let mut current_size = 0;
+        // オーバーラップ以外の文を含むか
+        let mut pending = false;
+
+        for (i, sentence) in sentences.iter().enumerate() {
+            let size = self.unit.measure(sentence);
+
+            if size > self.chunk_size {
+                if pending {
+                    chunks.push(current.concat().trim().to_string());
+                }
+                chunks.extend(self.split_chars(sentence.trim()));
+                current.clear();
+                current_size = 0;
+                pending = false;
+                continue;
+            }
+
+            // 段落がチャンクに収まらない場合は、チャンクが半分以上埋まっていれば段落の境界で区切る
+            let paragraph_start = i > 0 && sentences[i - 1].ends_with("\n\n");
+            let paragraph_size: usize =
+                sentences[i..].iter().take_while(|s| !s.ends_with("\n\n")).map(|s| self.unit.measure(s)).sum();
+            let break_paragraph = paragraph_start
+                && current_size + paragraph_size > self.chunk_size
+                && current_size * 2 >= self.chunk_size;
+
+            if pending && (current_size + size > self.chunk_size || break_paragraph) {
+                chunks.push(current.concat().trim().to_string());
+
+                let overlap = self.overlap_sentences(&current, size);
+                current.drain(..current.len() - overlap);
+                current_size = current.iter().map(|s| self.unit.measure(s)).sum();
+            }
+
+            current.push(sentence);
+            current_size += size;
+            pending = true;
+        }
+
+        if pending {
+            chunks.push(current.concat().trim().to_string());
+        }
+        chunks.retain(|chunk| !chunk.is_empty());
+        chunks
+    }
+
+    /// 次のチャンクに引き継ぐ末尾の文の数
+    fn overlap_sentences(&self, sentences: &[&str], next_size: usize) -> usize {
+        let mut size = 0;
+        let mut count = 0;
+        for sentence in sentences.iter().rev() {
+            let sentence_size = self.unit.measure(sentence);
+            if size + sentence_size > self.chunk_overlap || size + sentence_size + next_size > self.chunk_size {
+                break;
+            }
+            size += sentence_size;
+            count += 1;
+        }
+        count
+    }
+
+    fn split_chars(&self, text: &str) -> Vec<String> {
+        let units = self.unit.units(text);
         let mut chunks = Vec::new();
         let mut start = 0;
-        while start < chars.len() {
-            let end = usize::min(start + self.chunk_size, chars.len());
-            let chunk: String = chars[start..end].iter().collect();
-            chunks.push(chunk);
-            if end == chars.len() {
+        while start < units.len() {
+            let end = usize::min(start + self.chunk_size, units.len());
+            chunks.push(units[start..end].concat());
+            if end == units.len() {
                 break;
             }
             start += self.chunk_size - self.chunk_overlap;
@@ -125,6 +446,31 @@ impl TextSplitter {
     }
 }
 
+/// 区切り文字を含めて文に分割する ( 連結すると元のテキストになる )
+fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if !SENTENCE_DELIMITERS.contains(&c) {
+            continue;
+        }
+
+        let mut end = i + c.len_utf8();
+        while let Some((j, next)) = chars.next_if(|(_, next)| SENTENCE_TRAILERS.contains(next)) {
+            end = j + next.len_utf8();
+        }
+        sentences.push(&text[start..end]);
+        start = end;
+    }
+
+    if start < text.len() {
+        sentences.push(&text[start..]);
+    }
+    sentences
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -185,6 +531,116 @@ mod tests {
         .await;
     }
 
+    #[test]
+    fn markdown_blocks() {
+        let text =
+            format!("# API\n\n{}\n\n```rust\n{}```\n\n{}", "a".repeat(20), "let x = 1;\n".repeat(10), "b".repeat(20));
+        let sections = markdown::parse_sections(&text);
+        let splitter = TextSplitter::new(80, 8, SplitterKind::Char);
+
+        let chunks = splitter.split_blocks(&sections[0].blocks);
+
+        assert_eq!(chunks[0], format!("# API\n\n{}", "a".repeat(20)));
+        for chunk in &chunks[1..chunks.len() - 1] {
+            assert!(chunk.starts_with("```rust\n") && chunk.ends_with("\n```"), "Unclosed fence: {}", chunk);
+            assert!(chunk.chars().count() <= 80, "Unexpected chunk size");
+        }
+        assert_eq!(chunks[chunks.len() - 1], "b".repeat(20));
+    }
+
+    #[test]
+    fn sentence_splitter() {
+        let text = "これは一文目です。これは二文目です！\nこれは「三文目」です？\n\n次の段落の文です。とても長い文なので文字数で分割されることになります";
+        let splitter = TextSplitter::new(24, 10, SplitterKind::Sentence);
+
+        let chunks = splitter.split(text);
+
+        assert_eq!(
+            chunks,
+            vec![
+                "これは一文目です。これは二文目です！",
+                "これは二文目です！\nこれは「三文目」です？",
+                "次の段落の文です。",
+                "とても長い文なので文字数で分割されることになりま",
+                "割されることになります",
+            ]
+        );
+        assert_eq!(sentences(text).concat(), text);
+    }
+
+    #[test]
+    fn token_budget() {
+        let text = "これはテスト用のサンプルテキストです。".repeat(5) + &"This is a sample text. ".repeat(20);
+        let splitter = TextSplitter::new(30, 3, SplitterKind::Sentence).with_unit(ChunkUnit::Token);
+
+        let chunks = splitter.split(&text);
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(tokenizer::count_tokens(chunk) <= 30, "Unexpected chunk size: {}", chunk);
+        }
+        assert!(chunks[0].ends_with("。"));
+    }
+
+    #[tokio::test]
+    async fn heading_with_tiny_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("api.md");
+        std::fs::write(&path, "# API\n\n本文").unwrap();
+
+        // 見出しだけで予算を使い切っても分割が進む
+        for processor in [
+            DocumentProcessor::new(1000).with_heading_context(true).with_max_tokens(Some(1)),
+            DocumentProcessor::new(1).with_heading_context(true),
+        ] {
+            let (documents, _) = processor.process_file(dir.path(), &path).await.unwrap();
+            assert!(!documents.is_empty());
+            assert!(documents.iter().all(|document| document.content.starts_with("# API\n\n")));
+        }
+    }
+
+    /// 先頭の内容からの判定は拡張子のないファイルのみ
+    #[test]
+    fn sniff_without_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let mbox = "From a@example.com Wed May  1 10:00:00 2024\nSubject: a\n\nbody\n";
+        std::fs::write(dir.path().join("Inbox"), mbox).unwrap();
+        std::fs::write(dir.path().join("Inbox.bin"), mbox).unwrap();
+
+        let registry = ExtractorRegistry::with_defaults();
+        assert_eq!(registry.find(&dir.path().join("Inbox")).unwrap().name(), "mail");
+        assert!(registry.find(&dir.path().join("Inbox.bin")).is_none());
+    }
+
+    #[tokio::test]
+    async fn custom_extractor() {
+        struct CustomExtractor;
+
+        impl Extractor for CustomExtractor {
+            fn name(&self) -> &str {
+                "custom"
+            }
+
+            fn extensions(&self) -> &[&str] {
+                &["txt"]
+            }
+
+            fn extract(&self, path: &Path) -> Result<ExtractedDocument> {
+                Ok(ExtractedDocument::text(format!("custom: {}", std::fs::read_to_string(path)?)))
+            }
+        }
+
+        let testdata = Path::new("./testdata").canonicalize().unwrap();
+        let mut registry = ExtractorRegistry::with_defaults();
+        registry.register(CustomExtractor);
+        let processor = DocumentProcessor::new(1000).with_registry(registry);
+
+        let (documents, _) =
+            processor.process_file(&testdata.join("root1"), &testdata.join("root1/sample.txt")).await.unwrap();
+        assert!(documents[0].content.starts_with("custom: これはテスト用のサンプルテキストです"));
+        assert!(processor.scan(&testdata.join("root2")).unwrap().iter().any(|s| s.path == "pj1/sample.pdf"));
+    }
+
     async fn process_and_assert<P: AsRef<Path>>(
         root_dir: P,
         target_path: P,
@@ -0,0 +1,158 @@
+use anyhow::{anyhow, Context, Result};
+use roxmltree::Node;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// パッケージ内の関係を表す属性 ( `r:id` ) の名前空間
+pub const RELATIONSHIPS_NS: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+/// Office Open XML ( docx / xlsx / pptx ) のファイル
+pub struct Package {
+    archive: ZipArchive<File>,
+}
+
+/// パッケージ内のファイル間の関係 ( `_rels/*.rels` の 1 件 )
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relationship {
+    pub id: String,
+    /// 関係の種類の URI ( e.g. `.../relationships/notesSlide` )
+    pub kind: String,
+    /// パッケージ内のパス ( e.g. `ppt/notesSlides/notesSlide1.xml` )
+    pub target: String,
+}
+
+impl Package {
+    pub fn open(path: &Path) -> Result<Self> {
+        let archive =
+            ZipArchive::new(File::open(path)?).with_context(|| format!("Invalid package: {}", path.display()))?;
+        Ok(Self { archive })
+    }
+
+    /// パッケージ内のファイルの内容 ( 存在しない場合は None )
+    pub fn read(&mut self, name: &str) -> Result<Option<String>> {
+        let mut file = match self.archive.by_name(name) {
+            Ok(file) => file,
+            Err(ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut text = String::new();
+        file.read_to_string(&mut text).with_context(|| format!("Failed to read {}", name))?;
+        Ok(Some(text))
+    }
+
+    pub fn read_required(&mut self, name: &str) -> Result<String> {
+        self.read(name)?.ok_or_else(|| anyhow!("Missing part: {}", name))
+    }
+
+    /// part から参照しているファイル ( 外部リンクは除く )
+    pub fn relationships(&mut self, part: &str) -> Result<Vec<Relationship>> {
+        let (dir, name) = part.rsplit_once('/').unwrap_or(("", part));
+        let rels_path =
+            if dir.is_empty() { format!("_rels/{}.rels", name) } else { format!("{}/_rels/{}.rels", dir, name) };
+        let Some(xml) = self.read(&rels_path)? else {
+            return Ok(vec![]);
+        };
+
+        let document = roxmltree::Document::parse(&xml).with_context(|| format!("Invalid XML: {}", rels_path))?;
+        Ok(children(document.root_element(), "Relationship")
+            .filter(|rel| rel.attribute("TargetMode") != Some("External"))
+            .filter_map(|rel| {
+                Some(Relationship {
+                    id: rel.attribute("Id")?.to_string(),
+                    kind: rel.attribute("Type").unwrap_or_default().to_string(),
+                    target: resolve(dir, rel.attribute("Target")?),
+                })
+            })
+            .collect())
+    }
+}
+
+/// part のディレクトリからの相対パスをパッケージ内のパスにする
+fn resolve(dir: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+    let mut parts: Vec<&str> = dir.split('/').filter(|part| !part.is_empty()).collect();
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// 名前空間を除いた要素名が name の子要素
+pub fn children<'a, 'input: 'a>(node: Node<'a, 'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children().filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+pub fn child<'a, 'input: 'a>(node: Node<'a, 'input>, name: &'a str) -> Option<Node<'a, 'input>> {
+    children(node, name).next()
+}
+
+/// 名前空間を除いた属性名が name の属性 ( e.g. `w:val` )
+pub fn attribute<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes().find(|attribute| attribute.name() == name).map(|attribute| attribute.value())
+}
+
+/// 改行をまたがないように空白にまとめたテキスト ( 表のセルなど )
+pub fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// 0 始まりの列番号の列名 ( e.g. `0` → `A`, `27` → `AB` )
+pub fn column_name(mut index: usize) -> String {
+    let mut name = vec![];
+    loop {
+        name.push((b'A' + (index % 26) as u8) as char);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    name.iter().rev().collect()
+}
+
+#[cfg(test)]
+pub fn write_package(path: &Path, parts: &[(&str, &str)]) {
+    use std::io::Write;
+
+    let mut writer = zip::ZipWriter::new(File::create(path).unwrap());
+    for (name, content) in parts {
+        writer.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+        writer.write_all(content.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relationships() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.pptx");
+        let rels = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+            <Relationship Id="rId1" Type="http://x/notesSlide" Target="../notesSlides/notesSlide1.xml"/>
+            <Relationship Id="rId2" Type="http://x/image" Target="/ppt/media/image1.png"/>
+            <Relationship Id="rId3" Type="http://x/hyperlink" Target="https://example.com" TargetMode="External"/>
+        </Relationships>"#;
+        write_package(&path, &[("ppt/slides/_rels/slide1.xml.rels", rels), ("ppt/slides/slide1.xml", "<a/>")]);
+
+        let mut package = Package::open(&path).unwrap();
+        let targets: Vec<String> =
+            package.relationships("ppt/slides/slide1.xml").unwrap().into_iter().map(|rel| rel.target).collect();
+        assert_eq!(targets, vec!["ppt/notesSlides/notesSlide1.xml", "ppt/media/image1.png"]);
+        assert!(package.relationships("ppt/slides/slide2.xml").unwrap().is_empty());
+        assert_eq!(package.read("ppt/slides/slide1.xml").unwrap().as_deref(), Some("<a/>"));
+        assert!(package.read_required("missing.xml").is_err());
+    }
+}
@@ -1,18 +1,302 @@
+use crate::chroma::document::Attributes;
+use crate::document::extractor::{ExtractedDocument, Extractor, Section};
+use crate::warn;
 use anyhow::{Context, Result};
+use encoding_rs::EUC_JP;
+use pdf::content::{Op, TextDrawAdjusted};
+use pdf::encoding::BaseEncoding;
+use pdf::file::FileOptions;
+use pdf::font::{Font, ToUnicodeMap};
+use pdf::object::{MaybeRef, ParseOptions, Resolve};
+use pdf::primitive::Primitive;
+use serde_json::json;
+use std::collections::HashMap;
 use std::path::Path;
 use std::process::Command;
 
+/// テキストレイヤーとして扱う最低限の文字数 ( これ未満のページは OCR する )
+const MIN_TEXT_CHARS: usize = 10;
+
+/// テキストレイヤーとして扱う文字化けの割合の上限
+const MAX_GARBAGE_RATIO: f32 = 0.1;
+
+/// ページ単位の抽出結果
+#[derive(Debug)]
+pub struct Page {
+    /// 1 始まりのページ番号
+    pub number: usize,
+    pub text: String,
+    pub source: PageSource,
+}
+
+impl Page {
+    pub fn attributes(&self) -> Attributes {
+        Attributes::from([
+            ("page".to_string(), json!(self.number)),
+            ("extraction".to_string(), json!(self.source.as_str())),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageSource {
+    TextLayer,
+    Ocr,
+}
+
+impl PageSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PageSource::TextLayer => "text",
+            PageSource::Ocr => "ocr",
+        }
+    }
+}
+
+pub struct PdfExtractor;
+
+impl Extractor for PdfExtractor {
+    fn name(&self) -> &str {
+        "pdf"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pdf"]
+    }
+
+    fn sniff(&self, head: &[u8]) -> bool {
+        head.starts_with(b"%PDF-")
+    }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDocument> {
+        let sections =
+            extract_pages(path)?.into_iter().map(|page| Section::text(page.text.clone(), page.attributes())).collect();
+        Ok(ExtractedDocument { sections })
+    }
+}
+
 pub fn extract_text(path: &Path) -> Result<String> {
+    Ok(extract_pages(path)?.into_iter().map(|page| page.text + "\n").collect())
+}
+
+/// テキストレイヤーからページ単位でテキストを抽出し、テキストレイヤーが空または文字化けしているページのみ OCR する
+pub fn extract_pages(path: &Path) -> Result<Vec<Page>> {
+    let file = match FileOptions::uncached().parse_options(ParseOptions::tolerant()).open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            warn!("Failed to parse PDF, fallback to OCR: {} ( {} )", path.display(), e);
+            return ocr_all_pages(path);
+        }
+    };
+
+    let mut pages = Vec::new();
+    for (i, page) in file.pages().enumerate() {
+        let number = i + 1;
+        let text = match page.map_err(anyhow::Error::from).and_then(|page| page_text(&file, &page)) {
+            Ok(text) => text,
+            Err(e) => {
+                warn!("Failed to read text layer: {} p.{} ( {} )", path.display(), number, e);
+                String::new()
+            }
+        };
+
+        if is_readable(&text) {
+            pages.push(Page { number, text, source: PageSource::TextLayer });
+        } else {
+            pages.push(Page { number, text: ocr_page(path, number)?, source: PageSource::Ocr });
+        }
+    }
+
+    Ok(pages)
+}
+
+fn page_text(resolve: &impl Resolve, page: &pdf::object::Page) -> Result<String> {
+    let Some(contents) = &page.contents else { return Ok(String::new()) };
+
+    let mut fonts = HashMap::new();
+    for (name, font) in page.resources()?.fonts.iter() {
+        let to_unicode = font.to_unicode(resolve).transpose().unwrap_or(None);
+        let japan1 = font.encoding().is_some_and(|encoding| encoding.base == BaseEncoding::IdentityH)
+            && cid_ordering(resolve, font).as_deref() == Some("Japan1");
+        fonts.insert(name.as_str().to_string(), FontDecoder { to_unicode, two_byte: font.is_cid(), japan1 });
+    }
+
+    let mut text = String::new();
+    let mut font: Option<&FontDecoder> = None;
+    let mut line_y: Option<f32> = None;
+
+    for op in contents.operations(resolve)? {
+        match op {
+            Op::TextFont { name, .. } => font = fonts.get(name.as_str()),
+            Op::TextNewline | Op::EndText => new_line(&mut text),
+            Op::MoveTextPosition { translation } if translation.y != 0.0 => new_line(&mut text),
+            Op::SetTextMatrix { matrix } => {
+                if line_y.is_some_and(|y| y != matrix.f) {
+                    new_line(&mut text);
+                }
+                line_y = Some(matrix.f);
+            }
+            Op::TextDraw { text: s } => text.push_str(&decode(font, s.as_bytes())),
+            Op::TextDrawAdjusted { array } => {
+                for item in array {
+                    match item {
+                        TextDrawAdjusted::Text(s) => text.push_str(&decode(font, s.as_bytes())),
+                        // 大きな字間は英単語の区切りとみなす
+                        TextDrawAdjusted::Spacing(s) if s < -250.0 => {
+                            if text.chars().last().is_some_and(|c| c.is_ascii_alphanumeric()) {
+                                text.push(' ');
+                            }
+                        }
+                        TextDrawAdjusted::Spacing(_) => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(text.lines().map(|line| line.trim_end()).filter(|line| !line.is_empty()).collect::<Vec<_>>().join("\n"))
+}
+
+fn new_line(text: &mut String) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+struct FontDecoder {
+    to_unicode: Option<ToUnicodeMap>,
+    two_byte: bool,
+    /// 文字コードが Adobe-Japan1 の CID ( Identity-H ) のフォント
+    japan1: bool,
+}
+
+/// CID フォントの CIDSystemInfo の Ordering ( e.g. `Japan1` )
+fn cid_ordering(resolve: &impl Resolve, font: &MaybeRef<Font>) -> Option<String> {
+    let dictionary = |primitive: Primitive| primitive.resolve(resolve).and_then(|p| p.into_dictionary()).ok();
+    let font = dictionary(Primitive::Reference(font.as_ref()?.get_inner()))?;
+    let descendant =
+        font.get("DescendantFonts")?.clone().resolve(resolve).ok()?.into_array().ok()?.into_iter().next()?;
+    let system_info = dictionary(dictionary(descendant)?.get("CIDSystemInfo")?.clone())?;
+    system_info.get("Ordering")?.to_string_lossy().ok()
+}
+
+fn decode(font: Option<&FontDecoder>, bytes: &[u8]) -> String {
+    let Some(font) = font else { return String::from_utf8_lossy(bytes).to_string() };
+
+    match (&font.to_unicode, font.two_byte) {
+        (Some(map), true) => bytes
+            .chunks(2)
+            .map(|code| {
+                let code = code.iter().fold(0u16, |acc, b| (acc << 8) | *b as u16);
+                map.get(code).unwrap_or("\u{FFFD}").to_string()
+            })
+            .collect(),
+        (Some(map), false) => bytes.iter().map(|b| map.get(*b as u16).unwrap_or("\u{FFFD}").to_string()).collect(),
+        // 対応表のない CID フォントは、日本語の CID のみ文字の並びから復号する
+        (None, true) if font.japan1 => bytes
+            .chunks(2)
+            .map(|code| japan1_char(code.iter().fold(0u16, |acc, b| (acc << 8) | *b as u16)).unwrap_or('\u{FFFD}'))
+            .collect(),
+        (None, true) => "\u{FFFD}".repeat(bytes.len() / 2),
+        (None, false) if bytes.starts_with(&[0xfe, 0xff]) => pdf::font::utf16be_to_string_lossy(&bytes[2..]),
+        (None, false) => bytes.iter().map(|b| *b as char).collect(),
+    }
+}
+
+/// Adobe-Japan1 の CID に対応する文字
+///
+/// 英数字 ( CID 1〜95 )、半角カナ ( 327〜389 )、JIS X 0208 の文字 ( 633〜7477 ) のみ対応する。
+/// JIS X 0208 の文字は区点の順に CID が振られているため、区点に戻して EUC-JP として復号する
+fn japan1_char(cid: u16) -> Option<char> {
+    match cid {
+        61 => return Some('¥'),
+        95 => return Some('‾'),
+        1..=94 => return char::from_u32(0x20 + cid as u32 - 1),
+        327..=389 => return char::from_u32(0xFF61 + (cid - 327) as u32),
+        _ => {}
+    }
+
+    let (row, cell) = match cid {
+        633..=1124 => JAPAN1_NON_KANJI.iter().find_map(|(start, end, first)| {
+            let offset = cid.checked_sub(*first)?;
+            (offset <= end - start).then(|| ((start + offset) >> 8, (start + offset) & 0xFF))
+        })?,
+        // 第 1 水準 ( 47 区は 51 字 )、第 2 水準の順
+        1125..=4089 => (0x30 + (cid - 1125) / 94, 0x21 + (cid - 1125) % 94),
+        4090..=7477 => (0x50 + (cid - 4090) / 94, 0x21 + (cid - 4090) % 94),
+        _ => return None,
+    };
+    let bytes = [(row as u8) | 0x80, (cell as u8) | 0x80];
+    EUC_JP.decode_without_bom_handling_and_without_replacement(&bytes)?.chars().next()
+}
+
+/// 漢字以外の JIS X 0208 の文字 ( 区点の範囲と最初の CID )
+const JAPAN1_NON_KANJI: [(u16, u16, u16); 16] = [
+    (0x2121, 0x217E, 633),
+    (0x2221, 0x222E, 727),
+    (0x223A, 0x2241, 741),
+    (0x224A, 0x2250, 749),
+    (0x225C, 0x226A, 756),
+    (0x2272, 0x2279, 771),
+    (0x227E, 0x227E, 779),
+    (0x2330, 0x2339, 780),
+    (0x2341, 0x235A, 790),
+    (0x2361, 0x237A, 816),
+    (0x2421, 0x2473, 842),
+    (0x2521, 0x2576, 925),
+    (0x2621, 0x2638, 1011),
+    (0x2641, 0x2658, 1035),
+    (0x2721, 0x2741, 1059),
+    (0x2751, 0x2771, 1092),
+];
+
+/// テキストレイヤーの内容が検索に使える程度に読めるか
+fn is_readable(text: &str) -> bool {
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.len() < MIN_TEXT_CHARS {
+        return false;
+    }
+
+    let garbage =
+        chars.iter().filter(|c| **c == '\u{FFFD}' || c.is_control() || ('\u{E000}'..='\u{F8FF}').contains(*c)).count();
+    (garbage as f32 / chars.len() as f32) <= MAX_GARBAGE_RATIO
+}
+
+fn ocr_page(path: &Path, number: usize) -> Result<String> {
     // 一時ディレクトリの作成
     let temp_dir =
         tempfile::Builder::new().prefix("pdf_images_").tempdir().context("Failed to create temporary directory")?;
 
-    let temp_dir_path = temp_dir.path().to_str().unwrap();
-    let pdf_path = path.to_str().unwrap();
+    let image_prefix = temp_dir.path().join("page");
+    let number = number.to_string();
+
+    // 指定ページのみを画像に変換
+    let output = Command::new("pdftoppm")
+        .arg("-png")
+        .args(["-f", &number, "-l", &number, "-singlefile"])
+        .arg(path)
+        .arg(&image_prefix)
+        .output()
+        .context("Failed to convert PDF to images")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("PDF to image conversion failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    ocr_image(&image_prefix.with_extension("png"))
+}
+
+fn ocr_all_pages(path: &Path) -> Result<Vec<Page>> {
+    // 一時ディレクトリの作成
+    let temp_dir =
+        tempfile::Builder::new().prefix("pdf_images_").tempdir().context("Failed to create temporary directory")?;
 
     // PDFを画像に変換
     let output = Command::new("pdftoppm")
-        .args(["-png", pdf_path, &format!("{}/page", temp_dir_path)])
+        .arg("-png")
+        .arg(path)
+        .arg(temp_dir.path().join("page"))
         .output()
         .context("Failed to convert PDF to images")?;
 
@@ -20,32 +304,109 @@ pub fn extract_text(path: &Path) -> Result<String> {
         return Err(anyhow::anyhow!("PDF to image conversion failed: {}", String::from_utf8_lossy(&output.stderr)));
     }
 
-    // 変換された画像からテキストを抽出
-    let mut all_text = String::new();
-    let mut page_num = 1;
+    // ページ数に応じてファイル名がゼロ埋めされるため、ファイル名の順に処理する
+    let mut images: Vec<_> = std::fs::read_dir(temp_dir.path())?.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    images.sort();
 
-    loop {
-        let image_path = format!("{}/page-{}.png", temp_dir_path, page_num);
-        if !std::path::Path::new(&image_path).exists() {
-            break;
-        }
+    images
+        .iter()
+        .enumerate()
+        .map(|(i, image)| Ok(Page { number: i + 1, text: ocr_image(image)?, source: PageSource::Ocr }))
+        .collect()
+}
 
-        let output = Command::new("tesseract")
-            .args([&image_path, "stdout", "-l", "jpn"])
-            .output()
-            .context("Failed to perform OCR")?;
+fn ocr_image(image_path: &Path) -> Result<String> {
+    let output = Command::new("tesseract")
+        .arg(image_path)
+        .args(["stdout", "-l", "jpn"])
+        .output()
+        .context("Failed to perform OCR")?;
 
-        if !output.status.success() {
-            return Err(anyhow::anyhow!("OCR failed: {}", String::from_utf8_lossy(&output.stderr)));
-        }
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("OCR failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    String::from_utf8(output.stdout).context("Failed to decode OCR output")
+}
 
-        let page_text = String::from_utf8(output.stdout).context("Failed to decode OCR output")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        all_text.push_str(&page_text);
-        all_text.push('\n');
+    #[test]
+    fn text_layer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sample.pdf");
+        std::fs::write(&path, minimal_pdf(&["This is the first page text", "This is the second page text"])).unwrap();
 
-        page_num += 1;
+        let pages = extract_pages(&path).unwrap();
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1].number, 2);
+        assert_eq!(pages[1].text, "This is the second page text");
+        assert_eq!(pages[1].source, PageSource::TextLayer);
+    }
+
+    /// ToUnicode のない日本語の CID フォント ( Adobe-Japan1、Identity-H ) もテキストレイヤーを使う
+    #[test]
+    fn japan1_text_layer() {
+        let pages = extract_pages(Path::new("testdata/root2/pj1/sample.pdf")).unwrap();
+
+        assert_eq!(pages[0].source, PageSource::TextLayer);
+        let lines: Vec<&str> = pages[0].text.lines().collect();
+        assert_eq!(lines[0], "これはテスト用のサンプルテキストです。");
+        assert_eq!(lines[1], "複数行のテキストを含むファイルを作成します。");
+        assert!(lines.contains(&"特殊文字を含む"));
+
+        assert_eq!(japan1_char(1), Some(' '));
+        assert_eq!(japan1_char(34), Some('A'));
+        assert_eq!(japan1_char(633), Some('\u{3000}'));
+        assert_eq!(japan1_char(842), Some('ぁ'));
+        assert_eq!(japan1_char(1125), Some('亜'));
+        assert_eq!(japan1_char(4090), Some('弌'));
+        assert_eq!(japan1_char(7477), Some('瑤'));
+        assert_eq!(japan1_char(9000), None);
+    }
+
+    #[test]
+    fn readable() {
+        assert!(is_readable("これはテスト用のサンプルテキストです"));
+        assert!(!is_readable("12"));
+        assert!(!is_readable("\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}abc"));
     }
 
-    Ok(all_text)
+    /// 標準フォントでページごとに 1 行のテキストを描画する最小構成の PDF
+    fn minimal_pdf(texts: &[&str]) -> Vec<u8> {
+        let n = texts.len();
+        let kids = (0..n).map(|i| format!("{} 0 R", 4 + i * 2)).collect::<Vec<_>>().join(" ");
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids, n),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+        ];
+        for (i, text) in texts.iter().enumerate() {
+            let content = format!("BT /F1 12 Tf 72 720 Td ({}) Tj ET", text);
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                5 + i * 2
+            ));
+            objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content));
+        }
+
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = vec![];
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+        }
+        let xref = pdf.len();
+        pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+        for offset in offsets {
+            pdf.extend(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        pdf.extend(
+            format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).as_bytes(),
+        );
+        pdf
+    }
 }
@@ -0,0 +1,214 @@
+use crate::chroma::document::Attributes;
+use crate::document::extractor::{Block, ExtractedDocument, Extractor, Section};
+use crate::document::office::{self, attribute, child, children, Package, RELATIONSHIPS_NS};
+use anyhow::{anyhow, Context, Result};
+use roxmltree::Node;
+use serde_json::json;
+use std::path::Path;
+
+/// 本文として扱わないプレースホルダー ( スライド番号、日付、フッターなど )
+const IGNORED_PLACEHOLDERS: [&str; 5] = ["sldNum", "dt", "ftr", "hdr", "sldImg"];
+
+/// タイトルのプレースホルダー
+const TITLE_PLACEHOLDERS: [&str; 2] = ["title", "ctrTitle"];
+
+/// PowerPoint ( .pptx ) のスライドごとのテキストとノート
+///
+/// スライドのタイトルを見出しとして付与する
+pub struct PptxExtractor;
+
+impl Extractor for PptxExtractor {
+    fn name(&self) -> &str {
+        "pptx"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pptx"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDocument> {
+        let mut package = Package::open(path)?;
+        let relationships = package.relationships("ppt/presentation.xml")?;
+        let xml = package.read_required("ppt/presentation.xml")?;
+        let presentation = roxmltree::Document::parse(&xml).context("Invalid XML: ppt/presentation.xml")?;
+
+        let mut sections = vec![];
+        // 非表示のスライドも含め、表示順に番号を付ける
+        let slide_ids =
+            child(presentation.root_element(), "sldIdLst").into_iter().flat_map(|list| children(list, "sldId"));
+        for (i, slide_id) in slide_ids.enumerate() {
+            let number = i + 1;
+            let target = slide_id
+                .attribute((RELATIONSHIPS_NS, "id"))
+                .and_then(|id| relationships.iter().find(|rel| rel.id == id))
+                .ok_or_else(|| anyhow!("Missing slide: {}", number))?
+                .target
+                .clone();
+
+            let xml = package.read_required(&target)?;
+            let slide = roxmltree::Document::parse(&xml).with_context(|| format!("Invalid XML: {}", target))?;
+            let mut blocks = vec![];
+            let lines = text_lines(slide.root_element());
+            if !lines.is_empty() {
+                blocks.push(Block::Text(lines.join("\n")));
+            }
+
+            let notes = package.relationships(&target)?.into_iter().find(|rel| rel.kind.ends_with("/notesSlide"));
+            if let Some(notes) = notes {
+                let xml = package.read_required(&notes.target)?;
+                let notes =
+                    roxmltree::Document::parse(&xml).with_context(|| format!("Invalid XML: {}", notes.target))?;
+                let lines = text_lines(notes.root_element());
+                if !lines.is_empty() {
+                    blocks.push(Block::Text(format!("Notes:\n{}", lines.join("\n"))));
+                }
+            }
+            if blocks.is_empty() {
+                continue;
+            }
+
+            let mut attributes = Attributes::from([("slide".to_string(), json!(number))]);
+            if let Some(title) = title(slide.root_element()) {
+                attributes.insert("heading".to_string(), json!(title));
+            }
+            sections.push(Section { blocks, attributes });
+        }
+        Ok(ExtractedDocument { sections })
+    }
+}
+
+/// 図形や表の段落ごとのテキスト ( 表は 1 行を `セル | セル` の 1 行にまとめる )
+fn text_lines(node: Node) -> Vec<String> {
+    let mut lines = vec![];
+    collect_lines(node, &mut lines);
+    lines
+}
+
+fn collect_lines(node: Node, lines: &mut Vec<String>) {
+    for node in node.children().filter(Node::is_element) {
+        match node.tag_name().name() {
+            "sp" if placeholder(node).is_some_and(|kind| IGNORED_PLACEHOLDERS.contains(&kind)) => {}
+            "p" => {
+                let text = paragraph_text(node);
+                if !text.trim().is_empty() {
+                    lines.push(text);
+                }
+            }
+            "tbl" => {
+                for row in children(node, "tr") {
+                    let cells: Vec<String> =
+                        children(row, "tc").map(|cell| office::single_line(&text_lines(cell).join(" "))).collect();
+                    if cells.iter().any(|cell| !cell.is_empty()) {
+                        lines.push(cells.join(" | "));
+                    }
+                }
+            }
+            _ => collect_lines(node, lines),
+        }
+    }
+}
+
+fn paragraph_text(paragraph: Node) -> String {
+    let mut text = String::new();
+    for node in paragraph.descendants() {
+        match node.tag_name().name() {
+            "t" => text.push_str(node.text().unwrap_or_default()),
+            "br" => text.push('\n'),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// 図形のプレースホルダーの種類 ( e.g. `title`、種類のないプレースホルダーは `body` )
+fn placeholder<'a>(shape: Node<'a, '_>) -> Option<&'a str> {
+    let placeholder = child(child(child(shape, "nvSpPr")?, "nvPr")?, "ph")?;
+    Some(attribute(placeholder, "type").unwrap_or("body"))
+}
+
+fn title(slide: Node) -> Option<String> {
+    let shape = slide
+        .descendants()
+        .filter(|node| node.tag_name().name() == "sp")
+        .find(|shape| placeholder(*shape).is_some_and(|kind| TITLE_PLACEHOLDERS.contains(&kind)))?;
+    let title = office::single_line(&text_lines(shape).join(" "));
+    (!title.is_empty()).then_some(title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slide(shapes: &str) -> String {
+        format!(
+            r#"<p:sld xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" xmlns:p="http://schemas.openxmlformats.org/presentationml/2006/main"><p:cSld><p:spTree>{}</p:spTree></p:cSld></p:sld>"#,
+            shapes
+        )
+    }
+
+    fn shape(placeholder: &str, paragraphs: &[&str]) -> String {
+        let paragraphs: String = paragraphs.iter().map(|p| format!("<a:p><a:r><a:t>{}</a:t></a:r></a:p>", p)).collect();
+        format!(
+            r#"<p:sp><p:nvSpPr><p:cNvPr id="1" name="s"/><p:cNvSpPr/><p:nvPr>{}</p:nvPr></p:nvSpPr><p:txBody>{}</p:txBody></p:sp>"#,
+            placeholder, paragraphs
+        )
+    }
+
+    #[test]
+    fn pptx() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deck.pptx");
+        let presentation = r#"<p:presentation xmlns:p="http://schemas.openxmlformats.org/presentationml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+            <p:sldIdLst><p:sldId id="256" r:id="rId2"/><p:sldId id="257" r:id="rId3"/><p:sldId id="258" r:id="rId4"/></p:sldIdLst>
+        </p:presentation>"#;
+        let rels = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+            <Relationship Id="rId2" Type="http://x/slide" Target="slides/slide2.xml"/>
+            <Relationship Id="rId3" Type="http://x/slide" Target="slides/slide1.xml"/>
+            <Relationship Id="rId4" Type="http://x/slide" Target="slides/slide3.xml"/>
+        </Relationships>"#;
+        let slide_rels = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+            <Relationship Id="rId1" Type="http://x/relationships/notesSlide" Target="../notesSlides/notesSlide1.xml"/>
+        </Relationships>"#;
+        let table = r#"<p:graphicFrame><a:graphic><a:graphicData><a:tbl>
+            <a:tr><a:tc><a:txBody><a:p><a:r><a:t>項目</a:t></a:r></a:p></a:txBody></a:tc><a:tc><a:txBody><a:p><a:r><a:t>値</a:t></a:r></a:p></a:txBody></a:tc></a:tr>
+        </a:tbl></a:graphicData></a:graphic></p:graphicFrame>"#;
+        let first = slide(&[shape(r#"<p:ph type="ctrTitle"/>"#, &["はじめに"]), shape("", &[])].concat());
+        let second = slide(
+            &[
+                shape(r#"<p:ph type="title"/>"#, &["構成"]),
+                shape(r#"<p:ph idx="1"/>"#, &["サーバー", "クライアント"]),
+                shape(r#"<p:ph type="sldNum"/>"#, &["2"]),
+                table.to_string(),
+            ]
+            .concat(),
+        );
+        let notes =
+            slide(&[shape(r#"<p:ph type="sldImg"/>"#, &[]), shape(r#"<p:ph type="body"/>"#, &["補足"])].concat());
+        office::write_package(
+            &path,
+            &[
+                ("ppt/presentation.xml", presentation),
+                ("ppt/_rels/presentation.xml.rels", rels),
+                ("ppt/slides/slide2.xml", &first),
+                ("ppt/slides/slide1.xml", &second),
+                ("ppt/slides/_rels/slide1.xml.rels", slide_rels),
+                ("ppt/slides/slide3.xml", &slide("")),
+                ("ppt/notesSlides/notesSlide1.xml", &notes),
+            ],
+        );
+
+        let sections = PptxExtractor.extract(&path).unwrap().sections;
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].attributes.get("slide"), Some(&json!(1)));
+        assert_eq!(sections[0].attributes.get("heading"), Some(&json!("はじめに")));
+        assert_eq!(sections[1].attributes.get("slide"), Some(&json!(2)));
+        assert_eq!(sections[1].attributes.get("heading"), Some(&json!("構成")));
+        assert_eq!(
+            sections[1].blocks,
+            vec![
+                Block::Text("構成\nサーバー\nクライアント\n項目 | 値".to_string()),
+                Block::Text("Notes:\n補足".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use crate::chroma::document::Attributes;
+use crate::config::{TableConfig, TableMode};
+use crate::document::encoding;
+use crate::document::extractor::{Block, ExtractedDocument, Extractor, Section};
+use crate::document::office;
+use anyhow::{Context, Result};
+use encoding_rs::Encoding;
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// CSV / TSV / JSON / JSON Lines
+///
+/// `file` はファイル全体を 1 つの文書とし、`row` は 1 行 ( レコード ) ごとに区切って本文にしない列をメタデータ ( `column_<列名>` ) にする
+#[derive(Default)]
+pub struct TableExtractor {
+    config: TableConfig,
+    /// 文字コード ( 指定しない場合は自動判定 )
+    encoding: Option<&'static Encoding>,
+}
+
+/// 1 行 ( レコード ) 分の列名と値
+struct Record {
+    /// 1 始まりの行番号 ( JSON は配列の何番目か )
+    number: usize,
+    fields: Vec<(String, Value)>,
+}
+
+impl TableExtractor {
+    pub fn new(config: TableConfig, encoding: Option<&'static Encoding>) -> Self {
+        Self { config, encoding }
+    }
+
+    fn row_sections(&self, records: Vec<Record>) -> Vec<Section> {
+        let mut sections = vec![];
+        for record in records {
+            let is_text = |name: &str| self.config.text_columns.iter().any(|column| column == name);
+            // 本文にする列が 1 つもない場合は全ての列を本文にする
+            let all = !record.fields.iter().any(|(name, _)| is_text(name));
+            let (mut text, others): (Vec<_>, Vec<_>) =
+                record.fields.into_iter().partition(|(name, _)| all || is_text(name));
+            // JSON のキーの順序は保たれないため、本文は設定した列の順にする
+            text.sort_by_key(|(name, _)| self.config.text_columns.iter().position(|column| column == name));
+
+            let lines: Vec<String> = text.iter().map(|(name, value)| format!("{}: {}", name, display(value))).collect();
+            if lines.is_empty() {
+                continue;
+            }
+            // 列名が `row` や `page` などの決まったキーと重ならないように `column_` を付ける
+            let mut attributes: Attributes =
+                others.into_iter().map(|(name, value)| (format!("column_{}", name), value)).collect();
+            attributes.insert("row".to_string(), json!(record.number));
+            sections.push(Section::text(lines.join("\n"), attributes));
+        }
+        sections
+    }
+}
+
+impl Extractor for TableExtractor {
+    fn name(&self) -> &str {
+        "table"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["csv", "tsv", "json", "jsonl"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDocument> {
+        let (text, encoding) = encoding::read_text(path, self.encoding)?;
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+        let records = match extension.as_str() {
+            "csv" => delimited(&text, b',')?,
+            "tsv" => delimited(&text, b'\t')?,
+            "jsonl" => json_lines(&text)?,
+            _ => match serde_json::from_str(&text).context("Invalid JSON")? {
+                Value::Array(values) => values.into_iter().enumerate().map(|(i, value)| record(i + 1, value)).collect(),
+                // 配列でない JSON はレコードの集まりとして扱わない
+                value if self.config.mode == TableMode::File => {
+                    let block =
+                        Block::Code { code: serde_json::to_string_pretty(&value)?, fence: Some("```json".to_string()) };
+                    let sections = vec![Section { blocks: vec![block], attributes: Attributes::new() }];
+                    return Ok(ExtractedDocument { sections }.with_attribute("encoding", json!(encoding.name())));
+                }
+                value => vec![record(1, value)],
+            },
+        };
+
+        let sections = match self.config.mode {
+            TableMode::File => {
+                let lines: Vec<String> = records.iter().map(line).collect();
+                if lines.is_empty() {
+                    vec![]
+                } else {
+                    // 行の途中では分割しない
+                    let block = Block::Code { code: lines.join("\n"), fence: None };
+                    vec![Section { blocks: vec![block], attributes: Attributes::new() }]
+                }
+            }
+            TableMode::Row => self.row_sections(records),
+        };
+        Ok(ExtractedDocument { sections }.with_attribute("encoding", json!(encoding.name())))
+    }
+}
+
+/// 1 行目を見出しとする CSV / TSV ( 見出しが空の列は列名 ( e.g. `C` ) を使い、値が空の列は除く )
+fn delimited(text: &str, delimiter: u8) -> Result<Vec<Record>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(text.trim_start_matches('\u{feff}').as_bytes());
+    let header: Vec<String> = reader.headers()?.iter().map(|name| name.trim().to_string()).collect();
+
+    let mut records = vec![];
+    for row in reader.records() {
+        let row = row?;
+        let number = row.position().map(|position| position.line() as usize).unwrap_or(records.len() + 2);
+        let fields: Vec<(String, Value)> = row
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| !value.trim().is_empty())
+            .map(|(i, value)| {
+                let name =
+                    header.get(i).filter(|name| !name.is_empty()).cloned().unwrap_or_else(|| office::column_name(i));
+                (name, scalar(value.trim()))
+            })
+            .collect();
+        if !fields.is_empty() {
+            records.push(Record { number, fields });
+        }
+    }
+    Ok(records)
+}
+
+/// 空行を除く 1 行ごとの JSON
+fn json_lines(text: &str) -> Result<Vec<Record>> {
+    let mut records = vec![];
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value = serde_json::from_str(line).with_context(|| format!("Invalid JSON: line {}", i + 1))?;
+        records.push(record(i + 1, value));
+    }
+    Ok(records)
+}
+
+/// オブジェクトはキーを列名とし、それ以外の値は `value` 列とする ( null の列は除く )
+fn record(number: usize, value: Value) -> Record {
+    let fields = match value {
+        Value::Object(object) => object.into_iter().filter(|(_, value)| !value.is_null()).collect(),
+        Value::Null => vec![],
+        value => vec![("value".to_string(), value)],
+    };
+    Record { number, fields }
+}
+
+/// 数値や真偽値として絞り込めるように、文字列に戻しても変わらない値は変換する ( e.g. `007` は文字列のまま )
+fn scalar(value: &str) -> Value {
+    if let Ok(number) = value.parse::<i64>() {
+        if number.to_string() == value {
+            return json!(number);
+        }
+    }
+    if let Ok(number) = value.parse::<f64>() {
+        if number.is_finite() && number.to_string() == value {
+            return json!(number);
+        }
+    }
+    match value {
+        "true" | "TRUE" => json!(true),
+        "false" | "FALSE" => json!(false),
+        _ => json!(value),
+    }
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// `#行番号 列: 値 / 列: 値`
+fn line(record: &Record) -> String {
+    let cells: Vec<String> = record
+        .fields
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, office::single_line(&display(value))))
+        .collect();
+    format!("#{} {}", record.number, cells.join(" / "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("faq.csv");
+        std::fs::write(&path, "質問,回答,,件数\n\"ログイン できない\",\"パスワードを\n再設定する\",x,3\n,,,\n")
+            .unwrap();
+
+        let sections = TableExtractor::default().extract(&path).unwrap().sections;
+        assert_eq!(sections.len(), 1);
+        let code = "#2 質問: ログイン できない / 回答: パスワードを 再設定する / C: x / 件数: 3".to_string();
+        assert_eq!(sections[0].blocks, vec![Block::Code { code, fence: None }]);
+
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, r#"{"a": {"b": 1}}"#).unwrap();
+        let sections = TableExtractor::default().extract(&path).unwrap().sections;
+        let code = "{\n  \"a\": {\n    \"b\": 1\n  }\n}".to_string();
+        assert_eq!(sections[0].blocks, vec![Block::Code { code, fence: Some("```json".to_string()) }]);
+    }
+
+    #[test]
+    fn row() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tickets.jsonl");
+        let lines = [
+            r#"{"id": "007", "title": "遅い", "body": "検索が遅い", "priority": 2, "tags": ["db"], "closed": null}"#,
+            "",
+            r#"{"id": "008", "priority": 1}"#,
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let config = TableConfig { mode: TableMode::Row, text_columns: vec!["title".to_string(), "body".to_string()] };
+
+        let sections = TableExtractor::new(config, None).extract(&path).unwrap().sections;
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].blocks, vec![Block::Text("title: 遅い\nbody: 検索が遅い".to_string())]);
+        let attributes = &sections[0].attributes;
+        assert_eq!(attributes.get("row"), Some(&json!(1)));
+        assert_eq!(attributes.get("column_id"), Some(&json!("007")));
+        assert_eq!(attributes.get("column_priority"), Some(&json!(2)));
+        assert_eq!(attributes.get("column_tags"), Some(&json!(["db"])));
+        assert!(!attributes.contains_key("column_closed"));
+        // 本文にする列がない行は全ての列を本文にする
+        assert_eq!(sections[1].blocks, vec![Block::Text("id: 008\npriority: 1".to_string())]);
+        assert_eq!(sections[1].attributes.get("row"), Some(&json!(3)));
+
+        // 決まったキーと同じ名前の列も行番号を上書きしない
+        let path = dir.path().join("pages.csv");
+        std::fs::write(&path, "page,row,title\n12,x,目次\n").unwrap();
+        let config = TableConfig { mode: TableMode::Row, text_columns: vec!["title".to_string()] };
+        let sections = TableExtractor::new(config, None).extract(&path).unwrap().sections;
+        let attributes = &sections[0].attributes;
+        assert_eq!(attributes.get("row"), Some(&json!(2)));
+        assert_eq!(attributes.get("column_row"), Some(&json!("x")));
+        assert_eq!(attributes.get("column_page"), Some(&json!(12)));
+
+        assert_eq!(scalar("42"), json!(42));
+        assert_eq!(scalar("1.5"), json!(1.5));
+        assert_eq!(scalar("TRUE"), json!(true));
+        assert_eq!(scalar("1e3"), json!("1e3"));
+    }
+}
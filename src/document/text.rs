@@ -1,7 +1,37 @@
+use crate::document::encoding;
+use crate::document::extractor::{ExtractedDocument, Extractor};
 use anyhow::Result;
-use std::fs;
+use encoding_rs::Encoding;
+use serde_json::json;
 use std::path::Path;
 
+#[derive(Default)]
+pub struct TextExtractor {
+    /// 文字コード ( 指定しない場合は自動判定 )
+    encoding: Option<&'static Encoding>,
+}
+
+impl TextExtractor {
+    pub fn new(encoding: Option<&'static Encoding>) -> Self {
+        Self { encoding }
+    }
+}
+
+impl Extractor for TextExtractor {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["txt"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDocument> {
+        let (text, encoding) = encoding::read_text(path, self.encoding)?;
+        Ok(ExtractedDocument::text(text).with_attribute("encoding", json!(encoding.name())))
+    }
+}
+
 pub fn extract_text(path: &Path) -> Result<String> {
-    Ok(fs::read_to_string(path)?)
+    Ok(encoding::read_text(path, None)?.0)
 }
@@ -0,0 +1,64 @@
+/// 英数字の単語を 1 トークンとみなす最大の文字数
+const WORD_CHARS_PER_TOKEN: usize = 4;
+
+/// オフラインで使える近似的なトークン分割 ( 連結すると元のテキストになる )
+///
+/// 埋め込みモデルのトークナイザより多めに数えるように、日本語などの ASCII 以外の文字は 1 文字を 1 トークン、
+/// 英数字は 4 文字ごとに 1 トークン ( 直前の空白 1 つを含む )、連続する空白や記号はそれぞれ 1 トークンとする
+pub fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        let mut word = c.is_ascii_alphanumeric();
+
+        if c == ' ' {
+            if let Some((i, next)) = chars.next_if(|(_, next)| next.is_ascii_alphanumeric()) {
+                end = i + next.len_utf8();
+                word = true;
+            }
+        }
+
+        if word {
+            for _ in 1..WORD_CHARS_PER_TOKEN {
+                let Some((i, next)) = chars.next_if(|(_, next)| next.is_ascii_alphanumeric()) else { break };
+                end = i + next.len_utf8();
+            }
+        } else if c.is_whitespace() {
+            while let Some((i, next)) = chars.next_if(|(_, next)| next.is_whitespace()) {
+                end = i + next.len_utf8();
+            }
+        }
+
+        tokens.push(&text[start..end]);
+    }
+
+    tokens
+}
+
+pub fn count_tokens(text: &str) -> usize {
+    tokenize(text).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        let text = "Hello world, これはテスト です。\n\n  fn main()";
+
+        let tokens = tokenize(text);
+
+        assert_eq!(
+            tokens,
+            vec![
+                "Hell", "o", " worl", "d", ",", " ", "こ", "れ", "は", "テ", "ス", "ト", " ", "で", "す", "。",
+                "\n\n  ", "fn", " main", "(", ")"
+            ]
+        );
+        assert_eq!(tokens.concat(), text);
+        assert_eq!(count_tokens(""), 0);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::chroma::document::Attributes;
+use crate::document::extractor::{Block, ExtractedDocument, Extractor, Section};
+use crate::document::office::{self, attribute, child, children, Package, RELATIONSHIPS_NS};
+use anyhow::{anyhow, Context, Result};
+use roxmltree::Node;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Excel ( .xlsx ) のシートごとの行
+///
+/// 最初の空でない行を見出しとし、以降の各行を `#行番号 見出し: 値 / 見出し: 値` の 1 行にまとめる。
+/// 日付などの表示形式は反映せず、保存されている値をそのまま使う
+pub struct XlsxExtractor;
+
+/// 1 行分のセル ( 0 始まりの列番号と値 )
+type Row = BTreeMap<usize, String>;
+
+impl Extractor for XlsxExtractor {
+    fn name(&self) -> &str {
+        "xlsx"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["xlsx", "xlsm"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDocument> {
+        let mut package = Package::open(path)?;
+        let shared_strings = match package.read("xl/sharedStrings.xml")? {
+            Some(xml) => shared_strings(&xml)?,
+            None => vec![],
+        };
+        let relationships = package.relationships("xl/workbook.xml")?;
+        let xml = package.read_required("xl/workbook.xml")?;
+        let workbook = roxmltree::Document::parse(&xml).context("Invalid XML: xl/workbook.xml")?;
+        let sheets = child(workbook.root_element(), "sheets").ok_or_else(|| anyhow!("Missing sheets"))?;
+
+        let mut sections = vec![];
+        for sheet in children(sheets, "sheet") {
+            let name = attribute(sheet, "name").unwrap_or_default();
+            let target = sheet
+                .attribute((RELATIONSHIPS_NS, "id"))
+                .and_then(|id| relationships.iter().find(|rel| rel.id == id))
+                .ok_or_else(|| anyhow!("Missing sheet: {}", name))?;
+            let xml = package.read_required(&target.target)?;
+            let lines = row_lines(&sheet_rows(&xml, &shared_strings).with_context(|| format!("Sheet: {}", name))?);
+            if lines.is_empty() {
+                continue;
+            }
+
+            // 行の途中では分割しない
+            let block = Block::Code { code: lines.join("\n"), fence: None };
+            let attributes = Attributes::from([("sheet".to_string(), json!(name))]);
+            sections.push(Section { blocks: vec![block], attributes });
+        }
+        Ok(ExtractedDocument { sections })
+    }
+}
+
+/// 見出しの行を基準に、各行を見出しと値の組にする ( 見出しが空の列は列名 ( e.g. `C` ) を使う )
+fn row_lines(rows: &[(usize, Row)]) -> Vec<String> {
+    let Some((header_number, header)) = rows.first() else {
+        return vec![];
+    };
+
+    let mut lines = vec![format!("#{} {}", header_number, header.values().cloned().collect::<Vec<_>>().join(" / "))];
+    for (number, row) in &rows[1..] {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|(column, value)| {
+                let name = header.get(column).cloned().unwrap_or_else(|| office::column_name(*column));
+                format!("{}: {}", name, value)
+            })
+            .collect();
+        lines.push(format!("#{} {}", number, cells.join(" / ")));
+    }
+    lines
+}
+
+/// 空でないセルがある行 ( 1 始まりの行番号とセル )
+fn sheet_rows(xml: &str, shared_strings: &[String]) -> Result<Vec<(usize, Row)>> {
+    let document = roxmltree::Document::parse(xml)?;
+    let Some(data) = child(document.root_element(), "sheetData") else {
+        return Ok(vec![]);
+    };
+
+    let mut rows = vec![];
+    let mut number = 0;
+    for row in children(data, "row") {
+        number = attribute(row, "r").and_then(|r| r.parse().ok()).unwrap_or(number + 1);
+        let mut cells = Row::new();
+        for cell in children(row, "c") {
+            let column = attribute(cell, "r").and_then(column_index).unwrap_or(cells.len());
+            let value = office::single_line(&cell_value(cell, shared_strings));
+            if !value.is_empty() {
+                cells.insert(column, value);
+            }
+        }
+        if !cells.is_empty() {
+            rows.push((number, cells));
+        }
+    }
+    Ok(rows)
+}
+
+/// セルの値 ( 数式は保存されている計算結果 )
+fn cell_value(cell: Node, shared_strings: &[String]) -> String {
+    let value = child(cell, "v").and_then(|v| v.text()).unwrap_or_default();
+    match attribute(cell, "t") {
+        Some("s") => value.parse::<usize>().ok().and_then(|i| shared_strings.get(i)).cloned().unwrap_or_default(),
+        Some("inlineStr") => child(cell, "is").map(rich_text).unwrap_or_default(),
+        Some("b") => if value == "1" { "TRUE" } else { "FALSE" }.to_string(),
+        _ => value.to_string(),
+    }
+}
+
+fn shared_strings(xml: &str) -> Result<Vec<String>> {
+    let document = roxmltree::Document::parse(xml).context("Invalid XML: xl/sharedStrings.xml")?;
+    Ok(children(document.root_element(), "si").map(rich_text).collect())
+}
+
+/// 書式付きのテキスト ( ふりがなは除く )
+fn rich_text(node: Node) -> String {
+    node.descendants()
+        .filter(|node| node.tag_name().name() == "t")
+        .filter(|node| !node.ancestors().any(|ancestor| ancestor.tag_name().name() == "rPh"))
+        .filter_map(|node| node.text())
+        .collect()
+}
+
+/// セルの参照 ( e.g. `AB12` ) の 0 始まりの列番号
+fn column_index(reference: &str) -> Option<usize> {
+    let letters: Vec<char> = reference.chars().take_while(|c| c.is_ascii_alphabetic()).collect();
+    if letters.is_empty() {
+        return None;
+    }
+    let index = letters.iter().fold(0, |index, c| index * 26 + (c.to_ascii_uppercase() as usize - 'A' as usize + 1));
+    Some(index - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xlsx() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.xlsx");
+        let workbook = r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+            <sheets><sheet name="社員" sheetId="1" r:id="rId1"/><sheet name="空" sheetId="2" r:id="rId2"/></sheets>
+        </workbook>"#;
+        let rels = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+            <Relationship Id="rId1" Type="http://x/worksheet" Target="worksheets/sheet1.xml"/>
+            <Relationship Id="rId2" Type="http://x/worksheet" Target="/xl/worksheets/sheet2.xml"/>
+        </Relationships>"#;
+        let strings = r#"<sst xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+            <si><t>名前</t></si><si><r><t>年</t></r><r><t>齢</t></r></si><si><t>田中</t><rPh><t>タナカ</t></rPh></si>
+        </sst>"#;
+        let sheet1 = r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>
+            <row r="2"><c r="A2" t="s"><v>0</v></c><c r="B2" t="s"><v>1</v></c></row>
+            <row r="3"><c r="A3" t="s"><v>2</v></c><c r="B3"><v>30</v></c><c r="C3" t="b"><v>1</v></c></row>
+            <row r="4"><c r="B4"><f>B3+1</f><v>31</v></c><c r="AB4" t="inlineStr"><is><t>備考</t></is></c></row>
+        </sheetData></worksheet>"#;
+        let sheet2 =
+            r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData/></worksheet>"#;
+        office::write_package(
+            &path,
+            &[
+                ("xl/workbook.xml", workbook),
+                ("xl/_rels/workbook.xml.rels", rels),
+                ("xl/sharedStrings.xml", strings),
+                ("xl/worksheets/sheet1.xml", sheet1),
+                ("xl/worksheets/sheet2.xml", sheet2),
+            ],
+        );
+
+        let sections = XlsxExtractor.extract(&path).unwrap().sections;
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].attributes.get("sheet"), Some(&json!("社員")));
+        let expected = ["#2 名前 / 年齢", "#3 名前: 田中 / 年齢: 30 / C: TRUE", "#4 年齢: 31 / AB: 備考"].join("\n");
+        assert_eq!(sections[0].blocks, vec![Block::Code { code: expected, fence: None }]);
+
+        assert_eq!(column_index("AB12"), Some(27));
+        assert_eq!(office::column_name(27), "AB");
+        assert_eq!(office::column_name(0), "A");
+    }
+}
@@ -0,0 +1,219 @@
+use crate::embedder::Embedder;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// キャッシュのファイルの拡張子 ( `local` の保存先や進捗の記録の `.jsonl` と区別する )
+const EXTENSION: &str = "embcache.jsonl";
+
+/// (モデル, チャンクの内容のハッシュ) をキーとしてベクトルを保存するキャッシュ
+///
+/// モデルごとに 1 つの JSON Lines ファイルに追記する。
+/// 1 段落だけ変更したファイルや、複数のファイルで共通の定型文は、変わっていないチャンクをベクトル化しない
+pub struct CachedEmbedder {
+    inner: Arc<dyn Embedder>,
+    path: PathBuf,
+    entries: Mutex<HashMap<String, Vec<f32>>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+/// キャッシュの利用状況
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// キャッシュから取得したチャンク数
+    pub hits: usize,
+    /// ベクトル化したチャンク数
+    pub misses: usize,
+    /// 保存済みのベクトルの数
+    pub entries: usize,
+}
+
+/// 1 行分の保存内容
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    hash: String,
+    embedding: Vec<f32>,
+}
+
+impl CachedEmbedder {
+    pub fn open(dir: &Path, inner: Arc<dyn Embedder>) -> Result<Self> {
+        let path = dir.join(format!("{}.{}", file_stem(inner.model()), EXTENSION));
+        let entries = if path.exists() { read_entries(&path)? } else { HashMap::new() };
+        Ok(Self { inner, path, entries: Mutex::new(entries), hits: AtomicUsize::new(0), misses: AtomicUsize::new(0) })
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len(),
+        }
+    }
+
+    /// contents のいずれかに使われているベクトル以外と、他のモデルのキャッシュを削除する
+    ///
+    /// 削除したベクトルの数を返す
+    pub fn prune<'a>(&self, contents: impl IntoIterator<Item = &'a str>) -> Result<usize> {
+        let used: HashSet<String> = contents.into_iter().map(text_hash).collect();
+
+        let mut removed = 0;
+        let dir = self.path.parent().unwrap_or(Path::new("."));
+        if dir.exists() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path != self.path && is_cache_file(&path) {
+                    removed += read_entries(&path)?.len();
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let count = entries.len();
+        entries.retain(|hash, _| used.contains(hash));
+        removed += count - entries.len();
+        if count != entries.len() {
+            write_entries(&self.path, &entries)?;
+        }
+        Ok(removed)
+    }
+
+    fn append(&self, entries: &mut HashMap<String, Vec<f32>>, new_entries: Vec<Entry>) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut writer = BufWriter::new(OpenOptions::new().create(true).append(true).open(&self.path)?);
+        for entry in &new_entries {
+            writeln!(writer, "{}", serde_json::to_string(entry)?)?;
+        }
+        writer.flush()?;
+        entries.extend(new_entries.into_iter().map(|entry| (entry.hash, entry.embedding)));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Embedder for CachedEmbedder {
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    /// 検索クエリなどの 1 件ずつのベクトル化はキャッシュしない
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.inner.embed(text).await
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let hashes: Vec<String> = texts.iter().map(|text| text_hash(text)).collect();
+
+        // キャッシュになく、同じバッチ内でも重複しないものだけをベクトル化する
+        let mut missing = vec![];
+        {
+            let entries = self.entries.lock().unwrap();
+            let mut seen = HashSet::new();
+            for (text, hash) in texts.iter().zip(&hashes) {
+                if !entries.contains_key(hash) && seen.insert(hash) {
+                    missing.push((hash.clone(), text.clone()));
+                }
+            }
+        }
+
+        let misses = missing.len();
+        if !missing.is_empty() {
+            let missing_texts: Vec<String> = missing.iter().map(|(_, text)| text.clone()).collect();
+            let embeddings = self.inner.embed_batch(&missing_texts).await?;
+            let new_entries =
+                missing.into_iter().zip(embeddings).map(|((hash, _), embedding)| Entry { hash, embedding }).collect();
+            self.append(&mut self.entries.lock().unwrap(), new_entries)?;
+        }
+        self.hits.fetch_add(texts.len() - misses, Ordering::Relaxed);
+        self.misses.fetch_add(misses, Ordering::Relaxed);
+
+        let entries = self.entries.lock().unwrap();
+        Ok(hashes.iter().map(|hash| entries[hash].clone()).collect())
+    }
+}
+
+/// チャンクの内容の SHA-256 ( 16 進数 )
+fn text_hash(text: &str) -> String {
+    Sha256::digest(text.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// モデル名をファイル名に使える文字に置き換える ( e.g. `7shi/ezo-gemma-2-jpn:2b` → `7shi_ezo-gemma-2-jpn_2b` )
+fn file_stem(model: &str) -> String {
+    model.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' }).collect()
+}
+
+/// キャッシュのファイル ( 同じディレクトリにある他の `.jsonl` は対象外 )
+fn is_cache_file(path: &Path) -> bool {
+    path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.ends_with(&format!(".{}", EXTENSION)))
+}
+
+/// 書き込み途中で中断された行は無視する
+fn read_entries(path: &Path) -> Result<HashMap<String, Vec<f32>>> {
+    let mut entries = HashMap::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line.with_context(|| format!("Failed to read {}", path.display()))?;
+        if let Ok(entry) = serde_json::from_str::<Entry>(&line) {
+            entries.insert(entry.hash, entry.embedding);
+        }
+    }
+    Ok(entries)
+}
+
+/// 一時ファイルに書き出してから置き換える
+fn write_entries(path: &Path, entries: &HashMap<String, Vec<f32>>) -> Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let mut writer = BufWriter::new(File::create(&temp)?);
+    for (hash, embedding) in entries {
+        writeln!(writer, "{}", serde_json::to_string(&Entry { hash: hash.clone(), embedding: embedding.clone() })?)?;
+    }
+    writer.flush()?;
+    fs::rename(temp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::HashEmbedder;
+
+    #[tokio::test]
+    async fn cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = Arc::new(HashEmbedder::new(8));
+        let texts = |texts: &[&str]| texts.iter().map(|text| text.to_string()).collect::<Vec<_>>();
+
+        let embedder = CachedEmbedder::open(dir.path(), inner.clone()).unwrap();
+        let embeddings = embedder.embed_batch(&texts(&["共通", "a", "共通"])).await.unwrap();
+        assert_eq!(embeddings, vec![inner.embed_sync("共通"), inner.embed_sync("a"), inner.embed_sync("共通")]);
+        assert_eq!(embedder.stats(), CacheStats { hits: 1, misses: 2, entries: 2 });
+
+        // 再度開いても保存したベクトルを使う
+        let embedder = CachedEmbedder::open(dir.path(), inner.clone()).unwrap();
+        let embeddings = embedder.embed_batch(&texts(&["a", "b"])).await.unwrap();
+        assert_eq!(embeddings[1], inner.embed_sync("b"));
+        assert_eq!(embedder.stats(), CacheStats { hits: 1, misses: 1, entries: 3 });
+
+        // 他のモデルのキャッシュと使われていないベクトルを削除する
+        let other = CachedEmbedder::open(dir.path(), Arc::new(HashEmbedder::new(4))).unwrap();
+        other.embed_batch(&texts(&["a"])).await.unwrap();
+        // 同じディレクトリにある保存先のコレクションは削除しない
+        fs::write(dir.path().join("root.jsonl"), "{\"id\":\"a.txt-0\"}\n").unwrap();
+        assert_eq!(embedder.prune(["a", "b"]).unwrap(), 2);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+        assert!(dir.path().join("root.jsonl").exists());
+        let embedder = CachedEmbedder::open(dir.path(), inner.clone()).unwrap();
+        assert_eq!(embedder.stats().entries, 2);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::config::{Config, EmbeddingBackend};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use ollama_rs::generation::embeddings::request::{EmbeddingsInput, GenerateEmbeddingsRequest};
+use ollama_rs::Ollama;
+use std::sync::Arc;
+
+pub mod cache;
+
+pub use cache::{CacheStats, CachedEmbedder};
+
+/// テキストのベクトル化
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// ベクトルの生成方法を識別する名前 ( 名前が異なるベクトル同士は比較できない )
+    fn model(&self) -> &str;
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>>;
+
+    /// 複数のテキストをまとめてベクトル化する ( 結果は入力と同じ順 )
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for text in texts {
+            embeddings.push(self.embed(text).await?);
+        }
+        Ok(embeddings)
+    }
+}
+
+/// 設定に応じたベクトル化の方法
+pub fn from_config(config: &Config) -> Result<Arc<dyn Embedder>> {
+    Ok(match config.embedding.backend {
+        EmbeddingBackend::Ollama => Arc::new(OllamaEmbedder::new(config)?),
+        EmbeddingBackend::Hash => Arc::new(HashEmbedder::new(config.embedding.dimensions)),
+    })
+}
+
+/// Ollama の埋め込みモデル
+pub struct OllamaEmbedder {
+    ollama: Ollama,
+    model: String,
+}
+
+impl OllamaEmbedder {
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(Self { ollama: Ollama::try_new(config.ollama.url.as_str())?, model: config.ollama.embedding_model.clone() })
+    }
+}
+
+#[async_trait]
+impl Embedder for OllamaEmbedder {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let req = GenerateEmbeddingsRequest::new(self.model.clone(), EmbeddingsInput::Single(text.to_string()));
+        let result = self.ollama.generate_embeddings(req).await?;
+        result.embeddings.into_iter().next().ok_or_else(|| anyhow!("Empty embedding: {}", self.model))
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+        let req = GenerateEmbeddingsRequest::new(self.model.clone(), EmbeddingsInput::Multiple(texts.to_vec()));
+        let result = self.ollama.generate_embeddings(req).await?;
+        if result.embeddings.len() != texts.len() {
+            return Err(anyhow!(
+                "Unexpected embedding count: {} ( expected {} )",
+                result.embeddings.len(),
+                texts.len()
+            ));
+        }
+        Ok(result.embeddings)
+    }
+}
+
+/// 文字 n-gram をハッシュして数えるだけの決定的なベクトル化 ( モデルやネットワークが不要、テスト用 )
+pub struct HashEmbedder {
+    dimensions: usize,
+    model: String,
+}
+
+impl HashEmbedder {
+    /// 数える n-gram の長さ
+    const NGRAMS: [usize; 3] = [1, 2, 3];
+
+    pub fn new(dimensions: usize) -> Self {
+        let dimensions = dimensions.max(1);
+        Self { dimensions, model: format!("hash-ngram-{}", dimensions) }
+    }
+
+    pub fn embed_sync(&self, text: &str) -> Vec<f32> {
+        let chars: Vec<char> = text.to_lowercase().chars().collect();
+        let mut vector = vec![0.0; self.dimensions];
+
+        for n in Self::NGRAMS {
+            for gram in chars.windows(n).filter(|gram| !gram.iter().all(|c| c.is_whitespace())) {
+                let hash = fnv1a(&gram.iter().collect::<String>());
+                // 衝突の偏りを打ち消すために符号もハッシュから決める
+                let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+                vector[(hash % self.dimensions as u64) as usize] += sign;
+            }
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+}
+
+#[async_trait]
+impl Embedder for HashEmbedder {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        Ok(self.embed_sync(text))
+    }
+}
+
+/// 実行環境や Rust のバージョンに依存しないハッシュ
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash() {
+        let embedder = HashEmbedder::new(64);
+
+        let a = embedder.embed_sync("東京の天気");
+        let b = embedder.embed_sync("東京の天気");
+        let c = embedder.embed_sync("大阪の天気");
+        let d = embedder.embed_sync("Rust のコンパイラ");
+
+        assert_eq!(a.len(), 64);
+        assert_eq!(a, b);
+        assert!((a.iter().map(|x| x * x).sum::<f32>() - 1.0).abs() < 1e-5);
+        let dot = |x: &[f32], y: &[f32]| x.iter().zip(y).map(|(x, y)| x * y).sum::<f32>();
+        assert!(dot(&a, &c) > dot(&a, &d));
+        assert_eq!(embedder.embed_sync(""), vec![0.0; 64]);
+    }
+}
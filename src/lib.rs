@@ -1,6 +1,12 @@
 #[macro_use]
 pub mod chroma;
+pub mod chat;
+pub mod config;
 pub mod document;
+pub mod embedder;
 pub mod logger;
 pub mod ollama;
+pub mod pipeline;
+pub mod store;
+pub mod sync;
 pub mod utils;
@@ -1,83 +1,117 @@
+use crate::config::Config;
 use anyhow::Result;
-use ollama_rs::generation::completion::request::GenerationRequest;
+use futures::StreamExt;
+use ollama_rs::generation::chat::request::ChatMessageRequest;
+use ollama_rs::generation::chat::{ChatMessage, MessageRole};
 use ollama_rs::models::ModelOptions;
 use ollama_rs::Ollama;
-use futures::StreamExt;
 use std::io::{self, Write};
 
 pub struct OllamaClient {
     client: Ollama,
+    chat_model: String,
+    options: ModelOptions,
 }
 
 impl Default for OllamaClient {
     fn default() -> Self {
-        Self::new()
+        Self::new(&Config::default()).unwrap()
     }
 }
 
 impl OllamaClient {
-    pub fn new() -> Self {
-        Self { client: Ollama::new("http://localhost", 11434) }
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            client: Ollama::try_new(config.ollama.url.as_str())?,
+            chat_model: config.ollama.chat_model.clone(),
+            options: config.generation.to_model_options(),
+        })
     }
 
-    pub async fn streaming_answer(&self, query: &str, context: &[String]) -> Result<()> {
-        let prompt = format!(
-            "{}\n{}\n{}\n{}\n{}\n{}\n{}",
+    /// 会話履歴を踏まえて回答を生成し、生成しながら表示する
+    pub async fn streaming_answer(&self, history: &[ChatMessage], query: &str, context: &[String]) -> Result<String> {
+        let system = [
             "以下の [質問] に [参考情報] を踏まえ回答せよ",
             "回答内容の「根拠となる情報源・出典」を冒頭に必ず明示すること",
+            "情報源・出典には [参考情報] の各 [出典: ...] に記載されたファイルパスとチャンク番号をそのまま記載すること",
             "[参考情報] が回答の助けにならないと判断した場合は、憶測や不確かな回答を表示せず [与えられたコンテキストからは回答できません] とだけはっきり回答すること",
-            "[参考情報]",
-            context.join("\n"),
-            "[質問]",
-            query
-        );
-        let mut req = GenerationRequest::new("7shi/ezo-gemma-2-jpn:2b-instruct-q8_0".to_string(), prompt);
-
-        // 生成速度と品質のバランスを考慮したオプション設定
-        req.options = Some(
-            ModelOptions::default()
-                // 生成速度の最適化
-                // 高 → より創造的な出力になる, 低 → より決定論的な出力になる, default: 0
-                .mirostat(0)
-                // 高 → 並列処理が増えて高速化, 低 → シングルスレッドで低速, default: 自動検出
-                .num_thread(4)
-                // 高 → より長い文章を生成, 低 → より短い文章を生成, default: 128
-                .num_predict(128)
-                // 高 → 低確率トークンの影響を強く抑制, 低 → 低確率トークンも許容, default: 1.0
-                .tfs_z(1.0)
-                // 出力の決定論性向上
-                // 高 → より創造的で多様な出力, 低 → より決定論的で一貫性のある出力, default: 0.8
-                .temperature(0.5)
-                // 高 → より多様な単語を選択, 低 → より確実な単語を選択, default: 40
-                .top_k(20)
-                // 高 → より多様な文章を生成, 低 → より確実な文章を生成, default: 0.9
-                .top_p(0.7)
-                // 固定値 → 同じ入力に対して同じ出力を生成, 0 → ランダム, default: 0
-                .seed(42)
-                // ハードウェア最適化
-                // 高 → より多くのGPUを使用, 低 → より少ないGPUを使用, default: macOS = 1, 他 = 0
-                .num_gpu(0)
-                // 繰り返し制御
-                // 高 → より広い範囲で繰り返しを防止, 低 → より狭い範囲で繰り返しを防止, default: 64
-                .repeat_last_n(64)
-                // 高 → より強く繰り返しを抑制, 低 → より緩やかに繰り返しを抑制, default: 1.1
-                .repeat_penalty(1.1),
-        );
-
-        let mut stream = self.client.generate_stream(req).await?;
-
-        while let Some(chunks) = stream.next().await {
-            match chunks {
-                Ok(responses) => {
-                    for response in responses {
-                        print!("{}", response.response);
-                        io::stdout().flush()?;
-                    }
+        ]
+        .join("\n");
+        let prompt = format!("{}\n{}\n{}\n{}", "[参考情報]", context.join("\n"), "[質問]", query);
+
+        let mut messages = vec![ChatMessage::system(system)];
+        messages.extend_from_slice(history);
+        messages.push(ChatMessage::user(prompt));
+
+        let req = ChatMessageRequest::new(self.chat_model.clone(), messages).options(self.options.clone());
+        let mut stream = self.client.send_chat_messages_stream(req).await?;
+
+        let mut answer = String::new();
+        while let Some(response) = stream.next().await {
+            match response {
+                Ok(response) => {
+                    print!("{}", response.message.content);
+                    io::stdout().flush()?;
+                    answer.push_str(&response.message.content);
                 }
-                Err(e) => return Err(anyhow::anyhow!("Stream error: {}", e)),
+                Err(_) => return Err(anyhow::anyhow!("Stream error")),
             }
         }
 
-        Ok(())
+        Ok(answer)
+    }
+
+    /// 会話履歴がなくても意味が通じるように質問を書き換える ( e.g. 「それはなぜ？」 )
+    pub async fn rewrite_query(&self, history: &[ChatMessage], query: &str) -> Result<String> {
+        if history.is_empty() {
+            return Ok(query.to_string());
+        }
+
+        let prompt = rewrite_prompt(history, query);
+        let req = ChatMessageRequest::new(self.chat_model.clone(), vec![ChatMessage::user(prompt)])
+            .options(self.options.clone());
+        let rewritten = self.client.send_chat_messages(req).await?.message.content;
+
+        let rewritten = rewritten.trim();
+        Ok(if rewritten.is_empty() { query.to_string() } else { rewritten.to_string() })
+    }
+}
+
+fn rewrite_prompt(history: &[ChatMessage], query: &str) -> String {
+    let history = history
+        .iter()
+        .map(|message| match message.role {
+            MessageRole::User => format!("ユーザー: {}", message.content),
+            _ => format!("アシスタント: {}", message.content),
+        })
+        .collect::<Vec<_>>();
+
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        "以下の [会話履歴] を踏まえ、[質問] を会話履歴がなくても意味が通じる 1 つの質問に書き換えよ",
+        "指示語 ( それ、あれ、その など ) は具体的な語に置き換え、書き換えた質問のみを出力すること",
+        "[会話履歴]",
+        history.join("\n"),
+        "[質問]",
+        query
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrite() {
+        let history = vec![
+            ChatMessage::user("API の認証方式は？".to_string()),
+            ChatMessage::assistant("トークン認証です".to_string()),
+        ];
+
+        let prompt = rewrite_prompt(&history, "それはなぜ？");
+
+        assert!(prompt.contains(
+            "[会話履歴]\nユーザー: API の認証方式は？\nアシスタント: トークン認証です\n[質問]\nそれはなぜ？"
+        ));
     }
 }
@@ -0,0 +1,193 @@
+use crate::chroma::document::{CollectionName, Document};
+use crate::document::tokenizer::count_tokens;
+use crate::document::{DocumentProcessor, SourceFile};
+use crate::store::VectorStore;
+use crate::sync::{IndexedFile, Journal};
+use crate::{info, warn};
+use anyhow::Result;
+use futures::stream::{self, Stream, StreamExt};
+use std::path::Path;
+
+/// ベクトル化と保存の同時実行数のデフォルト
+pub const DEFAULT_EMBED_JOBS: usize = 2;
+
+/// テキスト抽出と分割の同時実行数のデフォルト ( CPU のコア数 )
+pub fn default_extract_jobs() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// 取り込む 1 ファイル分の作業
+pub struct Task<'a> {
+    pub source: &'a SourceFile,
+    /// 変更の場合の保存済みの情報 ( 保存する前に削除する )
+    pub indexed: Option<&'a IndexedFile>,
+}
+
+/// 1 ファイル分の処理結果
+pub struct Completed<'a> {
+    pub task: Task<'a>,
+    /// 保存したチャンク数
+    pub result: Result<usize>,
+}
+
+/// テキスト抽出 → 分割 → ベクトル化 → 保存 を段階ごとに並行して行う取り込み処理
+///
+/// 後段が詰まっている間は前段も次のファイルに進まないため、
+/// メモリ上に保持するのは高々 `extract_jobs + embed_jobs` ファイル分になる
+pub struct Pipeline<'a> {
+    processor: &'a DocumentProcessor,
+    store: &'a dyn VectorStore,
+    extract_jobs: usize,
+    embed_jobs: usize,
+    context_length: Option<usize>,
+    journal: Option<&'a Journal>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new(processor: &'a DocumentProcessor, store: &'a dyn VectorStore) -> Self {
+        Self {
+            processor,
+            store,
+            extract_jobs: default_extract_jobs(),
+            embed_jobs: DEFAULT_EMBED_JOBS,
+            context_length: None,
+            journal: None,
+        }
+    }
+
+    /// テキスト抽出 ( OCR を含む ) と分割の同時実行数
+    pub fn with_extract_jobs(mut self, jobs: usize) -> Self {
+        self.extract_jobs = jobs.max(1);
+        self
+    }
+
+    /// ベクトル化と保存の同時実行数 ( Ollama への同時リクエスト数 )
+    pub fn with_embed_jobs(mut self, jobs: usize) -> Self {
+        self.embed_jobs = jobs.max(1);
+        self
+    }
+
+    /// 埋め込みモデルのコンテキスト長 ( 超えるチャンクを警告する )
+    pub fn with_context_length(mut self, context_length: usize) -> Self {
+        self.context_length = Some(context_length);
+        self
+    }
+
+    /// 保存の開始と完了を記録する
+    pub fn with_journal(mut self, journal: &'a Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// 処理が終わったファイルから順に返す ( 順序は tasks と一致しない )
+    pub fn run<I>(self, root_path: &'a Path, tasks: I) -> impl Stream<Item = Completed<'a>> + 'a
+    where
+        I: IntoIterator<Item = Task<'a>>,
+        I::IntoIter: 'a,
+    {
+        let Self { processor, store, extract_jobs, embed_jobs, context_length, journal } = self;
+
+        stream::iter(tasks)
+            .map(move |task| async move {
+                // 差分更新の判定で計算した内容のハッシュは使い回す
+                let processed = processor.process_source(root_path, task.source).await;
+                if processed.is_ok() {
+                    info!("Converted: {}", task.source.path);
+                }
+                (task, processed)
+            })
+            .buffer_unordered(extract_jobs)
+            .map(move |(task, processed)| async move {
+                let result = match processed {
+                    Ok((documents, collection_name)) => {
+                        let journal = journal.zip(task.source.hash.get().map(String::as_str));
+                        save(store, &task, &documents, &collection_name, context_length, journal).await
+                    }
+                    Err(e) => Err(e),
+                };
+                Completed { task, result }
+            })
+            .buffer_unordered(embed_jobs)
+    }
+}
+
+/// 変更の場合は関連する情報を全て削除してから追加する
+async fn save(
+    store: &dyn VectorStore,
+    task: &Task<'_>,
+    documents: &[Document],
+    collection_name: &CollectionName,
+    context_length: Option<usize>,
+    journal: Option<(&Journal, &str)>,
+) -> Result<usize> {
+    if let Some(context_length) = context_length {
+        for document in documents {
+            // 埋め込みモデルのコンテキスト長を超える部分は切り詰められて検索に使われない
+            let tokens = count_tokens(&document.content);
+            if tokens > context_length {
+                warn!("Chunk exceeds embedding context: {} ( {} > {} tokens )", document.id, tokens, context_length);
+            }
+        }
+    }
+
+    if let Some((journal, hash)) = journal {
+        journal.start(task.source, hash)?;
+    }
+    if let Some(indexed) = task.indexed {
+        store.delete(&indexed.ids, &indexed.collection_name).await?;
+    }
+    store.save_batch(documents, collection_name).await?;
+    if let Some((journal, hash)) = journal {
+        journal.complete(task.source, hash, documents.len())?;
+    }
+    Ok(documents.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, StoreBackend};
+    use crate::embedder::HashEmbedder;
+    use crate::store;
+    use std::sync::Arc;
+
+    /// 並行して処理しても全てのファイルが 1 回ずつ保存される
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn concurrent() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.store.backend = StoreBackend::Local;
+        config.store.path = dir.path().to_path_buf();
+        config.store.batch_size = 2;
+        let store = store::open_with(&config, Arc::new(HashEmbedder::new(16))).await.unwrap();
+
+        let root = dir.path().join("docs");
+        std::fs::create_dir(&root).unwrap();
+        for i in 0..8 {
+            let text =
+                (0..i + 2).map(|j| format!("ファイル{}の{}番目の段落です。", i, j)).collect::<Vec<_>>().join("\n\n");
+            std::fs::write(root.join(format!("{}.txt", i)), text).unwrap();
+        }
+        let processor = DocumentProcessor::new(20);
+        let sources = processor.scan(&root).unwrap();
+        assert_eq!(sources.len(), 8);
+
+        // 同時実行数よりファイル数が多くても、全てのファイルが 1 回ずつ完了する
+        let pipeline = Pipeline::new(&processor, store.as_ref()).with_extract_jobs(3).with_embed_jobs(2);
+        let tasks = sources.iter().map(|source| Task { source, indexed: None });
+        let completed: Vec<_> = pipeline.run(&root, tasks).collect().await;
+
+        let mut paths: Vec<&str> = completed.iter().map(|c| c.task.source.path.as_str()).collect();
+        paths.sort();
+        let mut expected: Vec<&str> = sources.iter().map(|source| source.path.as_str()).collect();
+        expected.sort();
+        assert_eq!(paths, expected);
+        let saved: usize = completed.iter().map(|c| *c.result.as_ref().unwrap()).sum();
+        let mut stored = 0;
+        for collection in store.get_collections().await.unwrap() {
+            stored += collection.count;
+        }
+        assert!(saved > sources.len());
+        assert_eq!(saved, stored);
+    }
+}
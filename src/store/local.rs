@@ -0,0 +1,347 @@
+use crate::chroma::document::{CollectionName, Document, Metadata};
+use crate::config::Config;
+use crate::embedder::Embedder;
+use crate::store::{merge_hits, CollectionInfo, SearchHit, VectorStore};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// コレクションのファイルの拡張子
+const EXTENSION: &str = "jsonl";
+
+/// ローカルのディレクトリに保存する組み込みの保存先 ( Chroma DB が不要 )
+///
+/// コレクションごとに 1 つの JSON Lines ファイルに追記し、検索は全件のコサイン距離で行う
+pub struct LocalStore {
+    dir: PathBuf,
+    embedder: Arc<dyn Embedder>,
+    batch_size: usize,
+    collections: Mutex<BTreeMap<CollectionName, Collection>>,
+}
+
+/// 1 つのコレクションの保存内容 ( 追記された順 )
+#[derive(Debug, Default)]
+struct Collection {
+    entries: Vec<Entry>,
+    /// ID ごとの entries の位置
+    positions: HashMap<String, usize>,
+}
+
+impl Collection {
+    /// 同じ ID があれば上書きする
+    fn upsert(&mut self, entry: Entry) {
+        match self.positions.get(&entry.id) {
+            Some(position) => self.entries[*position] = entry,
+            None => {
+                self.positions.insert(entry.id.clone(), self.entries.len());
+                self.entries.push(entry);
+            }
+        }
+    }
+
+    fn retain(&mut self, f: impl FnMut(&Entry) -> bool) {
+        self.entries.retain(f);
+        self.positions = self.entries.iter().enumerate().map(|(i, entry)| (entry.id.clone(), i)).collect();
+    }
+}
+
+/// 1 行分の保存内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    id: String,
+    content: String,
+    metadata: Map<String, Value>,
+    embedding: Vec<f32>,
+}
+
+impl LocalStore {
+    pub fn new(config: &Config, embedder: Arc<dyn Embedder>) -> Result<Self> {
+        let dir = config.store.path.clone();
+        Ok(Self {
+            collections: Mutex::new(read_collections(&dir)?),
+            dir,
+            embedder,
+            batch_size: config.store.batch_size.max(1),
+        })
+    }
+
+    fn insert(&self, documents: &[Document], collection_name: &str, embeddings: Vec<Vec<f32>>) -> Result<()> {
+        let path = self.collection_path(collection_name)?;
+        let new_entries: Vec<Entry> = documents
+            .iter()
+            .zip(embeddings)
+            .map(|(document, embedding)| Entry {
+                id: document.id.clone(),
+                content: document.content.clone(),
+                metadata: document.metadata.to_map(),
+                embedding,
+            })
+            .collect();
+
+        let mut collections = self.collections.lock().unwrap();
+        fs::create_dir_all(&self.dir)?;
+        let mut writer = BufWriter::new(OpenOptions::new().create(true).append(true).open(&path)?);
+        for entry in &new_entries {
+            writeln!(writer, "{}", serde_json::to_string(entry)?)?;
+        }
+        writer.flush()?;
+
+        let collection = collections.entry(collection_name.to_string()).or_default();
+        for entry in new_entries {
+            collection.upsert(entry);
+        }
+        Ok(())
+    }
+
+    fn query(&self, embedding: &[f32], limit: usize, collection_names: &[&str]) -> Result<Vec<SearchHit>> {
+        let collections = self.collections.lock().unwrap();
+
+        let mut all_hits = Vec::new();
+        for collection_name in collection_names {
+            let collection = collections.get(*collection_name).ok_or_else(|| not_found(collection_name))?;
+
+            let mut hits: Vec<_> =
+                collection.entries.iter().map(|entry| (cosine_distance(embedding, &entry.embedding), entry)).collect();
+            hits.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+            all_hits.extend(hits.into_iter().take(limit).map(|(distance, entry)| SearchHit {
+                id: entry.id.clone(),
+                content: entry.content.clone(),
+                metadata: Metadata::from_map(entry.metadata.clone()),
+                distance,
+                collection: collection_name.to_string(),
+            }));
+        }
+
+        Ok(merge_hits(all_hits, limit))
+    }
+
+    fn collection_path(&self, collection_name: &str) -> Result<PathBuf> {
+        if collection_name.is_empty() || collection_name.contains(['/', '\\']) || collection_name.starts_with('.') {
+            return Err(anyhow!("Invalid collection name: {}", collection_name));
+        }
+        Ok(self.dir.join(format!("{}.{}", collection_name, EXTENSION)))
+    }
+}
+
+#[async_trait]
+impl VectorStore for LocalStore {
+    async fn get_collections(&self) -> Result<Vec<CollectionInfo>> {
+        let collections = self.collections.lock().unwrap();
+        Ok(collections
+            .iter()
+            .map(|(name, collection)| CollectionInfo { name: name.clone(), count: collection.entries.len() })
+            .collect())
+    }
+
+    async fn get_collection_documents(&self, collection_name: &str) -> Result<Vec<Document>> {
+        let collections = self.collections.lock().unwrap();
+        let collection = collections.get(collection_name).ok_or_else(|| not_found(collection_name))?;
+        Ok(collection
+            .entries
+            .iter()
+            .map(|entry| Document {
+                id: entry.id.clone(),
+                content: entry.content.clone(),
+                metadata: Metadata::from_map(entry.metadata.clone()),
+            })
+            .collect())
+    }
+
+    async fn get_collection_metadatas(&self, collection_name: &str) -> Result<Vec<(String, Metadata)>> {
+        let collections = self.collections.lock().unwrap();
+        let collection = collections.get(collection_name).ok_or_else(|| not_found(collection_name))?;
+        Ok(collection
+            .entries
+            .iter()
+            .map(|entry| (entry.id.clone(), Metadata::from_map(entry.metadata.clone())))
+            .collect())
+    }
+
+    async fn save_batch(&self, documents: &[Document], collection_name: &CollectionName) -> Result<()> {
+        for batch in documents.chunks(self.batch_size) {
+            let contents: Vec<String> = batch.iter().map(|document| document.content.clone()).collect();
+            let embeddings = self.embedder.embed_batch(&contents).await?;
+            self.insert(batch, collection_name, embeddings)?;
+        }
+        Ok(())
+    }
+
+    async fn update_metadata(
+        &self,
+        ids: &[String],
+        metadata: &Map<String, Value>,
+        collection_name: &str,
+    ) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let path = self.collection_path(collection_name)?;
+
+        let mut collections = self.collections.lock().unwrap();
+        let collection = collections.get_mut(collection_name).ok_or_else(|| not_found(collection_name))?;
+        for id in ids {
+            let Some(position) = collection.positions.get(id) else { continue };
+            collection.entries[*position].metadata.extend(metadata.clone());
+        }
+        write_entries(&path, &collection.entries)
+    }
+
+    async fn delete(&self, ids: &[String], collection_name: &str) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let path = self.collection_path(collection_name)?;
+
+        let mut collections = self.collections.lock().unwrap();
+        let collection = collections.get_mut(collection_name).ok_or_else(|| not_found(collection_name))?;
+        let ids: HashSet<&String> = ids.iter().collect();
+        collection.retain(|entry| !ids.contains(&entry.id));
+        write_entries(&path, &collection.entries)
+    }
+
+    async fn delete_collection(&self, collection_name: &str) -> Result<()> {
+        let path = self.collection_path(collection_name)?;
+
+        let mut collections = self.collections.lock().unwrap();
+        collections.remove(collection_name).ok_or_else(|| not_found(collection_name))?;
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    async fn count(&self, collection_name: &str) -> Result<usize> {
+        let collections = self.collections.lock().unwrap();
+        Ok(collections.get(collection_name).ok_or_else(|| not_found(collection_name))?.entries.len())
+    }
+
+    async fn search(&self, query: &str, limit: usize, collection_names: &[&str]) -> Result<Vec<SearchHit>> {
+        let embedding = self.embedder.embed(query).await?;
+        self.query(&embedding, limit, collection_names)
+    }
+}
+
+fn not_found(collection_name: &str) -> anyhow::Error {
+    anyhow!("Collection not found: {}", collection_name)
+}
+
+/// ディレクトリ内の全てのコレクション ( ディレクトリがなければ空 )
+fn read_collections(dir: &Path) -> Result<BTreeMap<CollectionName, Collection>> {
+    let mut collections = BTreeMap::new();
+    if !dir.exists() {
+        return Ok(collections);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == EXTENSION) {
+            let Some(name) = path.file_stem().and_then(|name| name.to_str()) else { continue };
+            collections.insert(name.to_string(), read_entries(&path)?);
+        }
+    }
+    Ok(collections)
+}
+
+/// 追記された順に読み、同じ ID は後の行で上書きする
+fn read_entries(path: &Path) -> Result<Collection> {
+    let mut collection = Collection::default();
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Entry =
+            serde_json::from_str(&line).with_context(|| format!("Failed to parse {}:{}", path.display(), i + 1))?;
+        collection.upsert(entry);
+    }
+    Ok(collection)
+}
+
+/// 一時ファイルに書き出してから置き換える
+fn write_entries(path: &Path, entries: &[Entry]) -> Result<()> {
+    let temp = path.with_extension(format!("{}.tmp", EXTENSION));
+    let mut writer = BufWriter::new(File::create(&temp)?);
+    for entry in entries {
+        writeln!(writer, "{}", serde_json::to_string(entry)?)?;
+    }
+    writer.flush()?;
+    fs::rename(temp, path)?;
+    Ok(())
+}
+
+/// 1 - コサイン類似度 ( 0 〜 2 、小さいほど近い )
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        1.0
+    } else {
+        1.0 - dot / norms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroma::document::{ChunkMetadata, FileMetadata, SearchMetadata};
+    use crate::embedder::HashEmbedder;
+    use chrono::DateTime;
+
+    #[tokio::test]
+    async fn persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.store.path = dir.path().join("store");
+
+        let store = LocalStore::new(&config, Arc::new(HashEmbedder::new(2))).unwrap();
+        store.insert(&[document("a.txt", 0, "東京")], "root", vec![vec![1.0, 0.0]]).unwrap();
+        store.insert(&[document("a.txt", 1, "大阪")], "root", vec![vec![0.0, 1.0]]).unwrap();
+        store.insert(&[document("b.txt", 0, "京都")], "pj1", vec![vec![0.8, 0.6]]).unwrap();
+        store.insert(&[document("a.txt", 1, "名古屋")], "root", vec![vec![0.0, 1.0]]).unwrap();
+
+        let hits = store.query(&[1.0, 0.1], 2, &["root", "pj1"]).unwrap();
+        assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["a.txt-0", "b.txt-0"]);
+        assert_eq!(hits[1].collection, "pj1");
+        assert!(store.query(&[1.0, 0.0], 2, &["unknown"]).is_err());
+
+        // 再度開いても同じ内容になる
+        let store = LocalStore::new(&config, Arc::new(HashEmbedder::new(2))).unwrap();
+        let collections = store.get_collections().await.unwrap();
+        assert_eq!(
+            collections.iter().map(|c| (c.name.as_str(), c.count)).collect::<Vec<_>>(),
+            vec![("pj1", 1), ("root", 2)]
+        );
+        let documents = store.get_collection_documents("root").await.unwrap();
+        assert_eq!(documents[1].content, "名古屋");
+        assert_eq!(documents[1].metadata.chunk.index, 1);
+        let metadatas = store.get_collection_metadatas("root").await.unwrap();
+        assert_eq!(metadatas.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(), vec!["a.txt-0", "a.txt-1"]);
+        assert_eq!(metadatas[1].1.file.path, "a.txt");
+
+        store.delete(&["a.txt-0".to_string()], "root").await.unwrap();
+        store.delete_collection("pj1").await.unwrap();
+
+        let store = LocalStore::new(&config, Arc::new(HashEmbedder::new(2))).unwrap();
+        assert_eq!(store.count("root").await.unwrap(), 1);
+        assert!(store.count("pj1").await.is_err());
+    }
+
+    fn document(path: &str, index: usize, content: &str) -> Document {
+        let now = DateTime::from_timestamp(0, 0).unwrap();
+        Document {
+            id: format!("{}-{}", path, index),
+            content: content.to_string(),
+            metadata: Metadata {
+                file: FileMetadata { path: path.to_string(), created_at: now, updated_at: now, hash: None },
+                chunk: ChunkMetadata { index, ..Default::default() },
+                search: SearchMetadata {},
+            },
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::chroma::document::{CollectionName, Document, Metadata};
+use crate::chroma::store::ChromaStore;
+use crate::config::{Config, StoreBackend};
+use crate::embedder::{self, Embedder};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+pub mod local;
+
+pub use local::LocalStore;
+
+#[derive(Debug)]
+pub struct CollectionInfo {
+    pub name: String,
+    pub count: usize,
+}
+
+/// 検索結果 ( distance が小さいほど関連度が高い )
+#[derive(Debug)]
+pub struct SearchHit {
+    pub id: String,
+    pub content: String,
+    pub metadata: Metadata,
+    pub distance: f32,
+    pub collection: CollectionName,
+}
+
+/// チャンクとベクトルの保存先
+#[async_trait]
+pub trait VectorStore: Send + Sync {
+    /// 名前順のコレクション一覧
+    async fn get_collections(&self) -> Result<Vec<CollectionInfo>>;
+
+    /// コレクション内の全てのチャンク
+    async fn get_collection_documents(&self, collection_name: &str) -> Result<Vec<Document>>;
+
+    /// コレクション内の全てのチャンクの ID とメタデータ ( 本文は取得しない )
+    async fn get_collection_metadatas(&self, collection_name: &str) -> Result<Vec<(String, Metadata)>>;
+
+    /// チャンクをまとめてベクトル化して保存する ( コレクションがなければ作成し、同じ ID は上書きする )
+    async fn save_batch(&self, documents: &[Document], collection_name: &CollectionName) -> Result<()>;
+
+    async fn save(&self, document: &Document, collection_name: &CollectionName) -> Result<()> {
+        self.save_batch(std::slice::from_ref(document), collection_name).await
+    }
+
+    /// チャンクのメタデータのうち指定したキーだけを上書きする ( 内容とベクトルはそのまま )
+    async fn update_metadata(&self, ids: &[String], metadata: &Map<String, Value>, collection_name: &str)
+        -> Result<()>;
+
+    async fn delete(&self, ids: &[String], collection_name: &str) -> Result<()>;
+
+    async fn delete_collection(&self, collection_name: &str) -> Result<()>;
+
+    async fn count(&self, collection_name: &str) -> Result<usize>;
+
+    /// 複数のコレクションから関連度の高い順に limit 件を返す
+    async fn search(&self, query: &str, limit: usize, collection_names: &[&str]) -> Result<Vec<SearchHit>>;
+}
+
+/// 設定に応じた保存先とベクトル化の方法
+pub async fn open(config: &Config) -> Result<Box<dyn VectorStore>> {
+    open_with(config, embedder::from_config(config)?).await
+}
+
+/// 設定に応じた保存先 ( ベクトル化の方法は指定する )
+pub async fn open_with(config: &Config, embedder: Arc<dyn Embedder>) -> Result<Box<dyn VectorStore>> {
+    Ok(match config.store.backend {
+        StoreBackend::Chroma => Box::new(ChromaStore::new(config, embedder).await?),
+        StoreBackend::Local => Box::new(LocalStore::new(config, embedder)?),
+    })
+}
+
+/// 複数コレクションの検索結果を距離順に統合し、同一内容を除いて上位 limit 件を返す
+pub(crate) fn merge_hits(mut hits: Vec<SearchHit>, limit: usize) -> Vec<SearchHit> {
+    hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+    let mut seen = std::collections::HashSet::new();
+    hits.retain(|hit| seen.insert(hit.content.clone()));
+    hits.truncate(limit);
+
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroma::document::{ChunkMetadata, FileMetadata, SearchMetadata};
+    use crate::document::DocumentProcessor;
+    use crate::sync::{Index, SyncPlan};
+    use chrono::DateTime;
+    use std::path::Path;
+
+    #[test]
+    fn merge_by_distance() {
+        let hits = vec![
+            hit("a", "aaa", 0.5, "c1"),
+            hit("b", "bbb", 0.1, "c1"),
+            hit("c", "ccc", 0.3, "c2"),
+            hit("d", "bbb", 0.2, "c2"),
+            hit("e", "eee", 0.9, "c2"),
+        ];
+
+        let merged = merge_hits(hits, 3);
+
+        assert_eq!(merged.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["b", "c", "a"]);
+        assert_eq!(merged[1].collection, "c2");
+    }
+
+    /// モデルやネットワークなしで testdata の読み込みから検索までを通す
+    #[tokio::test]
+    async fn load_and_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.store.backend = StoreBackend::Local;
+        config.store.path = dir.path().to_path_buf();
+        let store = open_with(&config, Arc::new(embedder::HashEmbedder::new(256))).await.unwrap();
+
+        let processor = DocumentProcessor::new(50);
+        for root in ["root1", "root3", "root4"] {
+            let root = Path::new("./testdata").join(root).canonicalize().unwrap();
+            for source in processor.scan(&root).unwrap() {
+                let (documents, collection_name) = processor.process_file(&root, &source.full_path).await.unwrap();
+                for document in &documents {
+                    store.save(document, &collection_name).await.unwrap();
+                }
+            }
+        }
+
+        let collections = store.get_collections().await.unwrap();
+        assert_eq!(collections.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), vec!["pj1-dir1", "root"]);
+
+        let hits = store.search("数字を含む 12345", 3, &["root", "pj1-dir1"]).await.unwrap();
+        assert!(hits[0].content.contains("12345"), "Unexpected hit: {}", hits[0].content);
+        assert!(hits.windows(2).all(|w| w[0].distance <= w[1].distance));
+
+        // 保存済みの情報と比較すると全て変更なしになる
+        let mut index = Index::new();
+        for collection in &collections {
+            index.extend(store.get_collection_metadatas(&collection.name).await.unwrap(), &collection.name);
+        }
+        let root = Path::new("./testdata/root1").canonicalize().unwrap();
+        let plan = SyncPlan::new(processor.scan(&root).unwrap(), index);
+        assert_eq!(plan.skipped.len(), 1);
+        assert!(plan.added.is_empty() && plan.updated.is_empty());
+    }
+
+    fn hit(id: &str, content: &str, distance: f32, collection: &str) -> SearchHit {
+        let now = DateTime::from_timestamp(0, 0).unwrap();
+        SearchHit {
+            id: id.to_string(),
+            content: content.to_string(),
+            metadata: Metadata {
+                file: FileMetadata { path: format!("{}.txt", id), created_at: now, updated_at: now, hash: None },
+                chunk: ChunkMetadata::default(),
+                search: SearchMetadata {},
+            },
+            distance,
+            collection: collection.to_string(),
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use crate::document::SourceFile;
+use crate::sync::SyncPlan;
+use crate::{info, warn};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// 設定ファイルのパスに付ける拡張子 ( e.g. local-vectored-llm.journal.jsonl )
+const EXTENSION: &str = "journal.jsonl";
+
+/// `load` の進捗を記録するファイル
+///
+/// ファイルごとに保存の開始と完了を追記していき、中断後の再実行では
+/// 完了していないファイルを読み込み直し、内容が変わっていないファイルは読み込まない
+pub struct Journal {
+    path: PathBuf,
+    records: Mutex<HashMap<String, Record>>,
+}
+
+/// 1 ファイル分の進捗 ( 同じファイルは後の行で上書きする )
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// 絶対パス
+    pub path: String,
+    /// ファイルの内容の SHA-256
+    pub hash: String,
+    pub updated_at: DateTime<Utc>,
+    /// 保存したチャンク数 ( 保存が完了していなければ None )
+    pub chunks: Option<usize>,
+}
+
+impl Record {
+    pub fn is_completed(&self) -> bool {
+        self.chunks.is_some()
+    }
+}
+
+impl Journal {
+    /// 設定ファイルと同じディレクトリの記録ファイル
+    pub fn path_for(config_path: &Path) -> PathBuf {
+        config_path.with_extension(EXTENSION)
+    }
+
+    /// 記録を読み込み、ファイルごとに最新の 1 行だけに詰めて書き直す
+    pub fn open(path: &Path) -> Result<Self> {
+        let records = if path.exists() { read_records(path)? } else { HashMap::new() };
+        let journal = Self { path: path.to_path_buf(), records: Mutex::new(records) };
+        journal.compact()?;
+        Ok(journal)
+    }
+
+    /// 記録を破棄して開く
+    pub fn reset(path: &Path) -> Result<Self> {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Self::open(path)
+    }
+
+    pub fn get(&self, path: &Path) -> Option<Record> {
+        self.records.lock().unwrap().get(&key(path)).cloned()
+    }
+
+    /// 保存を開始したことを記録する
+    pub fn start(&self, source: &SourceFile, hash: &str) -> Result<()> {
+        self.append(Record {
+            path: key(&source.full_path),
+            hash: hash.to_string(),
+            updated_at: source.updated_at,
+            chunks: None,
+        })
+    }
+
+    /// 保存が完了したことを記録する
+    pub fn complete(&self, source: &SourceFile, hash: &str, chunks: usize) -> Result<()> {
+        self.append(Record {
+            path: key(&source.full_path),
+            hash: hash.to_string(),
+            updated_at: source.updated_at,
+            chunks: Some(chunks),
+        })
+    }
+
+    /// 削除したファイルの記録を消す ( まとめて消してから 1 回だけ書き直す )
+    pub fn remove_all<P: AsRef<Path>>(&self, paths: &[P]) -> Result<()> {
+        let removed = {
+            let mut records = self.records.lock().unwrap();
+            paths.iter().filter(|path| records.remove(&key(path.as_ref())).is_some()).count()
+        };
+        if removed > 0 {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// 記録と突き合わせて差分更新の計画を修正する
+    ///
+    /// - 変更なしでも、保存が完了していない ( 途中で中断した ) ファイルは読み込み直す
+    /// - 更新日時が異なっても、前回保存した時から内容が変わっていないファイルは読み込まない
+    ///   ( コレクションが変わったファイルは内容が同じでも保存し直す )
+    pub fn resume(&self, plan: &mut SyncPlan) -> Result<()> {
+        let (skipped, incomplete): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut plan.skipped).into_iter().partition(|(source, indexed)| {
+                match self.get(&source.full_path) {
+                    Some(record) => record.chunks.is_some_and(|chunks| chunks <= indexed.ids.len()),
+                    // 記録がない ( 記録を始める前に保存した ) ファイルは保存済みの情報を信頼する
+                    None => true,
+                }
+            });
+        for (source, _) in &incomplete {
+            info!("Resume: {}", source.path);
+        }
+        plan.skipped = skipped;
+
+        let mut updated = vec![];
+        for (source, indexed) in std::mem::take(&mut plan.updated) {
+            if indexed.collection_name != source.collection_name {
+                updated.push((source, indexed));
+                continue;
+            }
+            match self.unchanged(&source) {
+                Ok(true) => {
+                    info!("Unchanged: {}", source.path);
+                    plan.skipped.push((source, indexed));
+                }
+                Ok(false) => updated.push((source, indexed)),
+                Err(e) => {
+                    warn!("Failed to check {}: {}", source.path, e);
+                    updated.push((source, indexed));
+                }
+            }
+        }
+        plan.updated = incomplete.into_iter().chain(updated).collect();
+
+        Ok(())
+    }
+
+    /// 前回保存が完了した時から内容が変わっていないか
+    fn unchanged(&self, source: &SourceFile) -> Result<bool> {
+        let Some(record) = self.get(&source.full_path).filter(|record| record.is_completed()) else {
+            return Ok(false);
+        };
+        if record.updated_at.timestamp() == source.updated_at.timestamp() {
+            return Ok(true);
+        }
+
+        let hash = source.content_hash()?;
+        if hash != record.hash {
+            return Ok(false);
+        }
+        // 次回は更新日時だけで判定できるようにする
+        self.complete(source, hash, record.chunks.unwrap_or_default())?;
+        Ok(true)
+    }
+
+    fn append(&self, record: Record) -> Result<()> {
+        let mut records = self.records.lock().unwrap();
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        // 中断されても直前の行までは残るように 1 行ずつ書き込む
+        writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        records.insert(record.path.clone(), record);
+        Ok(())
+    }
+
+    /// 一時ファイルに書き出してから置き換える
+    fn compact(&self) -> Result<()> {
+        let records = self.records.lock().unwrap();
+        if records.is_empty() {
+            if self.path.exists() {
+                fs::remove_file(&self.path)?;
+            }
+            return Ok(());
+        }
+
+        let mut records: Vec<_> = records.values().collect();
+        records.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let temp = self.path.with_extension(format!("{}.tmp", EXTENSION));
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        for record in records {
+            writeln!(writer, "{}", serde_json::to_string(record)?)?;
+        }
+        writer.flush()?;
+        fs::rename(temp, &self.path)?;
+        Ok(())
+    }
+}
+
+fn key(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// 書き込み途中で中断された最後の行は無視する
+fn read_records(path: &Path) -> Result<HashMap<String, Record>> {
+    let mut records = HashMap::new();
+    let lines: Vec<String> = BufReader::new(File::open(path)?).lines().collect::<Result<_, _>>()?;
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Record>(line) {
+            Ok(record) => {
+                records.insert(record.path.clone(), record);
+            }
+            Err(_) if i + 1 == lines.len() => warn!("Ignored incomplete line: {}:{}", path.display(), i + 1),
+            Err(e) => return Err(e).with_context(|| format!("Failed to parse {}:{}", path.display(), i + 1)),
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::{file_hash, IndexedFile};
+
+    #[test]
+    fn resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = source(dir.path(), "a.txt", "aaa", 100);
+        let b = source(dir.path(), "b.txt", "bbb", 100);
+        let c = source(dir.path(), "c.txt", "ccc", 100);
+        let d = source(dir.path(), "d.txt", "ddd", 100);
+        let e = source(dir.path(), "e.txt", "eee", 100);
+
+        let path = Journal::path_for(&dir.path().join("config.toml"));
+        let journal = Journal::open(&path).unwrap();
+        // a: 完了、b: 途中で中断、c: 完了後に内容は同じまま更新日時が変更、d: 完了後に内容が変更、
+        // e: 完了後にコレクションが変更
+        journal.complete(&a, &file_hash(&a.full_path).unwrap(), 2).unwrap();
+        journal.start(&b, &file_hash(&b.full_path).unwrap()).unwrap();
+        journal.complete(&c, &file_hash(&c.full_path).unwrap(), 1).unwrap();
+        journal.complete(&d, &file_hash(&d.full_path).unwrap(), 1).unwrap();
+        journal.complete(&e, &file_hash(&e.full_path).unwrap(), 1).unwrap();
+        drop(journal);
+        // 書き込み途中の行
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"path\":").unwrap();
+
+        let c = SourceFile { updated_at: DateTime::from_timestamp(200, 0).unwrap(), ..c };
+        fs::write(&d.full_path, "changed").unwrap();
+        let d = SourceFile { updated_at: DateTime::from_timestamp(200, 0).unwrap(), ..d };
+        let e = SourceFile { collection_name: "docs".to_string(), ..e };
+
+        let mut plan = SyncPlan {
+            skipped: vec![(a, indexed("a.txt", 2)), (b, indexed("b.txt", 1))],
+            updated: vec![(c, indexed("c.txt", 1)), (d, indexed("d.txt", 1)), (e, indexed("e.txt", 1))],
+            ..Default::default()
+        };
+        let journal = Journal::open(&path).unwrap();
+        journal.resume(&mut plan).unwrap();
+
+        let paths = |files: &[(SourceFile, IndexedFile)]| files.iter().map(|(s, _)| s.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&plan.skipped), vec!["a.txt", "c.txt"]);
+        assert_eq!(paths(&plan.updated), vec!["b.txt", "d.txt", "e.txt"]);
+        assert_eq!(journal.get(&dir.path().join("c.txt")).unwrap().updated_at.timestamp(), 200);
+
+        journal.remove_all(&[dir.path().join("a.txt"), dir.path().join("missing.txt")]).unwrap();
+        let journal = Journal::open(&path).unwrap();
+        assert!(journal.get(&dir.path().join("a.txt")).is_none());
+        assert!(!journal.get(&dir.path().join("b.txt")).unwrap().is_completed());
+
+        Journal::reset(&path).unwrap();
+        assert!(!path.exists());
+    }
+
+    fn source(dir: &Path, path: &str, content: &str, updated_at: i64) -> SourceFile {
+        let full_path = dir.join(path);
+        fs::write(&full_path, content).unwrap();
+        SourceFile {
+            full_path,
+            path: path.to_string(),
+            updated_at: DateTime::from_timestamp(updated_at, 0).unwrap(),
+            collection_name: "root".to_string(),
+            hash: Default::default(),
+        }
+    }
+
+    fn indexed(path: &str, chunks: usize) -> IndexedFile {
+        IndexedFile {
+            collection_name: "root".to_string(),
+            updated_at: DateTime::from_timestamp(100, 0).unwrap(),
+            hash: None,
+            ids: (0..chunks).map(|i| format!("{}-{}", path, i)).collect(),
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::chroma::document::{CollectionName, Metadata};
+use crate::document::SourceFile;
+use crate::store::VectorStore;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+pub mod journal;
+
+pub use journal::Journal;
+
+/// 保存済みのファイル単位の情報
+#[derive(Debug)]
+pub struct IndexedFile {
+    pub collection_name: CollectionName,
+    pub updated_at: DateTime<Utc>,
+    /// ファイルの内容の SHA-256 ( ハッシュを保存する前に保存したファイルは None )
+    pub hash: Option<String>,
+    pub ids: Vec<String>,
+}
+
+/// ファイルパスをキーとした保存済みファイルの一覧
+#[derive(Debug, Default)]
+pub struct Index {
+    files: HashMap<String, IndexedFile>,
+}
+
+impl Index {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// チャンクの ID とメタデータをファイルごとにまとめて追加する
+    pub fn extend(&mut self, metadatas: Vec<(String, Metadata)>, collection_name: &str) {
+        for (id, metadata) in metadatas {
+            let file = self.files.entry(metadata.file.path).or_insert_with(|| IndexedFile {
+                collection_name: collection_name.to_string(),
+                updated_at: metadata.file.updated_at,
+                hash: metadata.file.hash,
+                ids: vec![],
+            });
+            file.ids.push(id);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+/// ファイルの内容の SHA-256 ( 16 進数 )
+pub fn file_hash(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// 走査したファイルと保存済みの情報を突き合わせた差分更新の計画
+#[derive(Debug, Default)]
+pub struct SyncPlan {
+    /// 新規ファイル
+    pub added: Vec<SourceFile>,
+    /// 更新日時が不一致のファイル ( 保存済みの情報は削除してから追加する )
+    pub updated: Vec<(SourceFile, IndexedFile)>,
+    /// ディレクトリに存在しなくなったファイル
+    pub deleted: Vec<(String, IndexedFile)>,
+    /// 変更のないファイル
+    pub skipped: Vec<(SourceFile, IndexedFile)>,
+}
+
+impl SyncPlan {
+    pub fn new(sources: Vec<SourceFile>, mut index: Index) -> Self {
+        let mut plan = Self::default();
+
+        for source in sources {
+            match index.files.remove(&source.path) {
+                None => plan.added.push(source),
+                Some(indexed) if indexed.collection_name != source.collection_name => {
+                    plan.updated.push((source, indexed))
+                }
+                // メタデータは秒単位で保存されているため秒単位で比較する
+                Some(indexed) if indexed.updated_at.timestamp() == source.updated_at.timestamp() => {
+                    plan.skipped.push((source, indexed))
+                }
+                // git checkout やコピーで更新日時だけが変わった場合は内容で比較する
+                Some(indexed) if indexed.hash.is_some() && indexed.hash.as_deref() == source.content_hash().ok() => {
+                    plan.skipped.push((source, indexed))
+                }
+                Some(indexed) => plan.updated.push((source, indexed)),
+            }
+        }
+
+        plan.deleted = index.files.into_iter().collect();
+        plan.deleted.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        plan
+    }
+
+    /// 変更のないファイルも保存済みの情報を削除して読み込み直す
+    pub fn restart(&mut self) {
+        self.updated.append(&mut self.skipped);
+    }
+
+    /// 内容が同じため、更新日時が異なってもスキップしたファイル
+    pub fn touched(&self) -> impl Iterator<Item = &(SourceFile, IndexedFile)> {
+        self.skipped.iter().filter(|(source, indexed)| indexed.updated_at.timestamp() != source.updated_at.timestamp())
+    }
+}
+
+/// 保存済みの更新日時を書き換え、次回からは内容を読まずに更新日時だけで判定できるようにする
+pub async fn touch(store: &dyn VectorStore, source: &SourceFile, indexed: &IndexedFile) -> Result<()> {
+    let mut metadata = Map::new();
+    metadata.insert("file_updated_at".to_string(), json!(source.updated_at.timestamp()));
+    store.update_metadata(&indexed.ids, &metadata, &indexed.collection_name).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroma::document::{ChunkMetadata, Document, FileMetadata, SearchMetadata};
+    use crate::config::Config;
+    use crate::embedder::HashEmbedder;
+    use crate::store::LocalStore;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    #[test]
+    fn plan() {
+        let mut index = Index::new();
+        index.extend(vec![chunk("a.txt", 0, 100), chunk("a.txt", 1, 100), chunk("b.txt", 0, 100)], "root");
+        index.extend(vec![chunk("pj1/c.txt", 0, 100)], "pj1");
+
+        let plan = SyncPlan::new(
+            vec![source("a.txt", 100, "root"), source("b.txt", 200, "root"), source("d.txt", 100, "root")],
+            index,
+        );
+
+        assert_eq!(plan.added.iter().map(|s| s.path.as_str()).collect::<Vec<_>>(), vec!["d.txt"]);
+        assert_eq!(plan.skipped.iter().map(|(s, _)| s.path.as_str()).collect::<Vec<_>>(), vec!["a.txt"]);
+        assert_eq!(plan.updated.len(), 1);
+        assert_eq!(plan.updated[0].0.path, "b.txt");
+        assert_eq!(plan.updated[0].1.ids, vec!["b.txt-0"]);
+        assert_eq!(plan.deleted.len(), 1);
+        assert_eq!(plan.deleted[0].0, "pj1/c.txt");
+        assert_eq!(plan.deleted[0].1.collection_name, "pj1");
+    }
+
+    /// 更新日時が異なっても内容が同じならスキップする
+    #[test]
+    fn content_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "aaa").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "changed").unwrap();
+        std::fs::write(dir.path().join("c.txt"), "ccc").unwrap();
+        let hash = |content: &str| {
+            std::fs::write(dir.path().join("tmp"), content).unwrap();
+            file_hash(&dir.path().join("tmp")).ok()
+        };
+
+        let mut index = Index::new();
+        for (path, content) in [("a.txt", Some("aaa")), ("b.txt", Some("bbb")), ("c.txt", None)] {
+            let (id, mut metadata) = chunk(path, 0, 100);
+            metadata.file.hash = content.and_then(hash);
+            index.extend(vec![(id, metadata)], "root");
+        }
+
+        let sources = ["a.txt", "b.txt", "c.txt"]
+            .map(|path| SourceFile { full_path: dir.path().join(path), ..source(path, 200, "root") });
+        let plan = SyncPlan::new(sources.into(), index);
+
+        assert_eq!(plan.skipped.iter().map(|(s, _)| s.path.as_str()).collect::<Vec<_>>(), vec!["a.txt"]);
+        assert_eq!(plan.updated.iter().map(|(s, _)| s.path.as_str()).collect::<Vec<_>>(), vec!["b.txt", "c.txt"]);
+        // 比較に使ったハッシュは保存時に使い回す
+        assert_eq!(plan.skipped[0].0.hash.get().cloned(), hash("aaa"));
+        assert!(plan.updated[1].0.hash.get().is_none());
+    }
+
+    /// 内容が同じで更新日時だけが変わったファイルは、次回から更新日時だけでスキップする
+    #[tokio::test]
+    async fn touch_updated_at() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.store.path = dir.path().join("store");
+        let store = LocalStore::new(&config, Arc::new(HashEmbedder::new(8))).unwrap();
+
+        let full_path = dir.path().join("a.txt");
+        std::fs::write(&full_path, "aaa").unwrap();
+        let (id, mut metadata) = chunk("a.txt", 0, 100);
+        metadata.file.hash = file_hash(&full_path).ok();
+        store.save(&Document { id, content: "aaa".to_string(), metadata }, &"root".to_string()).await.unwrap();
+
+        let plan = |full_path: PathBuf| async {
+            let mut index = Index::new();
+            index.extend(store.get_collection_metadatas("root").await.unwrap(), "root");
+            SyncPlan::new(vec![SourceFile { full_path, ..source("a.txt", 200, "root") }], index)
+        };
+        let first = plan(full_path.clone()).await;
+        assert_eq!(first.skipped.len(), 1);
+        for (source, indexed) in first.touched() {
+            touch(&store, source, indexed).await.unwrap();
+        }
+
+        // 読めないファイルでも内容を比較せずにスキップする
+        let second = plan(dir.path().join("missing.txt")).await;
+        assert_eq!(second.skipped.len(), 1);
+        assert_eq!(second.touched().count(), 0);
+    }
+
+    fn chunk(path: &str, index: usize, updated_at: i64) -> (String, Metadata) {
+        let updated_at = DateTime::from_timestamp(updated_at, 0).unwrap();
+        let metadata = Metadata {
+            file: FileMetadata { path: path.to_string(), created_at: updated_at, updated_at, hash: None },
+            chunk: ChunkMetadata { index, ..Default::default() },
+            search: SearchMetadata {},
+        };
+        (format!("{}-{}", path, index), metadata)
+    }
+
+    fn source(path: &str, updated_at: i64, collection_name: &str) -> SourceFile {
+        SourceFile {
+            full_path: PathBuf::from(path),
+            path: path.to_string(),
+            // ナノ秒の差は無視される
+            updated_at: DateTime::from_timestamp(updated_at, 123).unwrap(),
+            collection_name: collection_name.to_string(),
+            hash: Default::default(),
+        }
+    }
+}